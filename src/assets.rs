@@ -0,0 +1,663 @@
+//! Loads models, textures and shader programs by path, relative to an asset
+//! root, and shares them between everything that asks for the same one.
+//!
+//! Loading hands out a typed `Handle` and counts a reference; each load is
+//! paired with a `release`, and the asset (and its GL object) goes away with
+//! the last one.
+//!
+//! With workers started, `request_model` and `request_texture` return at
+//! once with an empty model or a placeholder texture behind the handle, and
+//! parse or decode on the pool. `update` then swaps in what has finished,
+//! uploading textures on the calling thread, which has to own the GL context.
+
+use crate::import::{self, ImportError};
+use crate::model::Model;
+use crate::shaders;
+use crate::texture::{self, LoadOptions, Texture, TextureError, TextureImages};
+use crate::workers::{JobResult, WorkerPool};
+use gl::types::GLuint;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+/// Refers to an asset of type `T` in the `AssetManager` that returned it.
+pub struct Handle<T> {
+    index: usize,
+    marker: PhantomData<fn() -> T>,
+}
+
+// derived impls would require `T` to implement the traits as well
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Handle<T> {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Handle<T>) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Handle({})", self.index)
+    }
+}
+
+/// A linked shader program, deleted when dropped.
+#[derive(Debug)]
+pub struct Shader {
+    pub program: GLuint,
+}
+
+impl Drop for Shader {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteProgram(self.program);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AssetError {
+    Io(String, io::Error),
+    Model(ImportError),
+    Texture(TextureError),
+    /// A background load that panicked, with its message.
+    Panicked(String, String),
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssetError::Io(file, err) => write!(f, "{}: {}", file, err),
+            AssetError::Model(err) => write!(f, "{}", err),
+            AssetError::Texture(err) => write!(f, "{}", err),
+            AssetError::Panicked(file, message) => {
+                write!(f, "{}: loading panicked: {}", file, message)
+            }
+        }
+    }
+}
+
+impl Error for AssetError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AssetError::Io(_, err) => Some(err),
+            AssetError::Model(err) => Some(err),
+            AssetError::Texture(err) => Some(err),
+            AssetError::Panicked(..) => None,
+        }
+    }
+}
+
+impl From<ImportError> for AssetError {
+    fn from(err: ImportError) -> AssetError {
+        AssetError::Model(err)
+    }
+}
+
+impl From<TextureError> for AssetError {
+    fn from(err: TextureError) -> AssetError {
+        AssetError::Texture(err)
+    }
+}
+
+/// Shown until a requested texture has been decoded and uploaded.
+const PLACEHOLDER_COLOR: [u8; 3] = [96, 96, 96];
+
+/// Background loads finished out of all requested so far.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Progress {
+    pub finished: usize,
+    pub total: usize,
+}
+
+impl Progress {
+    pub fn is_done(&self) -> bool {
+        self.finished == self.total
+    }
+}
+
+/// What a worker hands back.
+enum Loaded {
+    Model(Result<Model, ImportError>),
+    /// With the path, for errors found while uploading.
+    Texture(String, Result<TextureImages, TextureError>),
+}
+
+/// Where a background load ends up once finished.
+enum Request {
+    Model(Handle<Model>),
+    Texture(Handle<Texture>, LoadOptions),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AssetKind {
+    Model,
+    Texture,
+    Shader,
+}
+
+/// One live asset, for memory reports.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssetUsage {
+    pub kind: AssetKind,
+    /// Resolved path, or the key of a generated asset.
+    pub path: String,
+    pub references: usize,
+    /// Estimated memory in bytes, 0 for shaders.
+    pub size: usize,
+}
+
+impl fmt::Display for AssetUsage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:>10.1} KiB  {:?} {} ({} references)",
+            self.size as f64 / 1024.0,
+            self.kind,
+            self.path,
+            self.references
+        )
+    }
+}
+
+struct Entry<T> {
+    key: String,
+    path: String,
+    asset: T,
+    references: usize,
+    size: usize,
+}
+
+/// Assets of one type, by key.
+struct Store<T> {
+    entries: Vec<Option<Entry<T>>>,
+    indices: HashMap<String, usize>,
+}
+
+impl<T> Store<T> {
+    fn new() -> Store<T> {
+        Store {
+            entries: vec![],
+            indices: HashMap::new(),
+        }
+    }
+
+    /// The asset under `key`, with one more reference, if it is loaded.
+    fn acquire(&mut self, key: &str) -> Option<Handle<T>> {
+        let index = *self.indices.get(key)?;
+        self.entries[index].as_mut()?.references += 1;
+
+        Some(Handle {
+            index,
+            marker: PhantomData,
+        })
+    }
+
+    fn insert(&mut self, key: String, path: String, asset: T, size: usize) -> Handle<T> {
+        let index = self.entries.len();
+        self.indices.insert(key.clone(), index);
+        self.entries.push(Some(Entry {
+            key,
+            path,
+            asset,
+            references: 1,
+            size,
+        }));
+
+        Handle {
+            index,
+            marker: PhantomData,
+        }
+    }
+
+    /// Puts `asset` behind `handle`, unless it was released meanwhile.
+    fn replace(&mut self, handle: Handle<T>, asset: T, size: usize) {
+        if let Some(entry) = &mut self.entries[handle.index] {
+            entry.asset = asset;
+            entry.size = size;
+        }
+    }
+
+    fn get(&self, handle: Handle<T>) -> &T {
+        &self.entries[handle.index]
+            .as_ref()
+            .expect("asset used after its last release")
+            .asset
+    }
+
+    /// Drops a reference, and the asset with the last one. Returns whether
+    /// it was freed.
+    fn release(&mut self, handle: Handle<T>) -> bool {
+        let slot = &mut self.entries[handle.index];
+        let entry = slot.as_mut().expect("asset released too often");
+        entry.references -= 1;
+
+        if entry.references > 0 {
+            return false;
+        }
+
+        self.indices.remove(&entry.key);
+        *slot = None;
+        true
+    }
+
+    fn usage(&self, kind: AssetKind) -> impl Iterator<Item = AssetUsage> + '_ {
+        self.entries.iter().flatten().map(move |entry| AssetUsage {
+            kind,
+            path: entry.path.clone(),
+            references: entry.references,
+            size: entry.size,
+        })
+    }
+}
+
+pub struct AssetManager {
+    root: PathBuf,
+    models: Store<Model>,
+    textures: Store<Texture>,
+    shaders: Store<Shader>,
+    workers: Option<WorkerPool<Loaded>>,
+    /// Background loads by job id, with the path for errors.
+    requests: HashMap<usize, (String, Request)>,
+    /// Loads run right away for want of workers, taken in like finished ones.
+    loaded_inline: Vec<(usize, JobResult<Loaded>)>,
+    progress: Progress,
+}
+
+impl AssetManager {
+    /// Relative paths are looked up under `root`. It is made absolute, so
+    /// paths already resolved against it (e.g. texture paths from a material
+    /// library) resolve to themselves.
+    pub fn new(root: impl Into<PathBuf>) -> AssetManager {
+        let root = root.into();
+
+        AssetManager {
+            root: root.canonicalize().unwrap_or(root),
+            models: Store::new(),
+            textures: Store::new(),
+            shaders: Store::new(),
+            workers: None,
+            requests: HashMap::new(),
+            loaded_inline: vec![],
+            progress: Progress::default(),
+        }
+    }
+
+    /// Starts the worker pool for `request_model` and `request_texture`,
+    /// one thread per core but the render thread's.
+    pub fn start_workers(&mut self) {
+        self.workers = Some(WorkerPool::with_available_parallelism());
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// `path` under the asset root, absolute paths are left alone.
+    pub fn resolve(&self, path: &str) -> String {
+        self.root.join(path).to_string_lossy().into_owned()
+    }
+
+    /// A model file in any format `import` reads.
+    pub fn load_model(&mut self, path: &str) -> Result<Handle<Model>, AssetError> {
+        let path = self.resolve(path);
+        if let Some(handle) = self.models.acquire(&path) {
+            return Ok(handle);
+        }
+
+        let model = import::load(&path)?;
+        Ok(self.insert_model(path.clone(), path, model))
+    }
+
+    /// A model made in code, e.g. a generated sphere, shared under `key`.
+    /// `make` only runs the first time.
+    pub fn model_with(&mut self, key: &str, make: impl FnOnce() -> Model) -> Handle<Model> {
+        let key = format!("generated:{}", key);
+        if let Some(handle) = self.models.acquire(&key) {
+            return handle;
+        }
+
+        self.insert_model(key.clone(), key, make())
+    }
+
+    fn insert_model(&mut self, key: String, path: String, model: Model) -> Handle<Model> {
+        let size = model_size(&model);
+        self.models.insert(key, path, model, size)
+    }
+
+    /// Like `load_model`, but parsed in the background (or right away if no
+    /// workers were started). The model is empty until `update` fills it in.
+    pub fn request_model(&mut self, path: &str) -> Handle<Model> {
+        let path = self.resolve(path);
+        if let Some(handle) = self.models.acquire(&path) {
+            return handle;
+        }
+
+        let empty = Model {
+            meshes: vec![],
+            materials: vec![],
+        };
+        let handle = self.models.insert(path.clone(), path.clone(), empty, 0);
+        self.submit(path.clone(), Request::Model(handle), move || {
+            Loaded::Model(import::load(&path))
+        });
+
+        handle
+    }
+
+    /// Like `load_texture`, but decoded in the background (or right away if
+    /// no workers were started). A grey placeholder stands in until `update`
+    /// uploads the image, or a white pixel if it fails to load.
+    pub fn request_texture(&mut self, path: &str, options: &LoadOptions) -> Handle<Texture> {
+        let path = self.resolve(path);
+        let key = format!("{} {:?}", path, options);
+        if let Some(handle) = self.textures.acquire(&key) {
+            return handle;
+        }
+
+        let placeholder = texture::load_solid(PLACEHOLDER_COLOR);
+        let size = placeholder.size_in_bytes();
+        let handle = self.textures.insert(key, path.clone(), placeholder, size);
+        self.submit(
+            path.clone(),
+            Request::Texture(handle, *options),
+            move || {
+                let images = texture::read(&path);
+                Loaded::Texture(path, images)
+            },
+        );
+
+        handle
+    }
+
+    fn submit(
+        &mut self,
+        path: String,
+        request: Request,
+        job: impl FnOnce() -> Loaded + Send + 'static,
+    ) {
+        let id = self.progress.total;
+        self.progress.total += 1;
+        self.requests.insert(id, (path, request));
+
+        match &self.workers {
+            Some(workers) => workers.submit(id, job),
+            None => self.loaded_inline.push((id, Ok(job()))),
+        }
+    }
+
+    /// Takes in the background loads finished since the last call, and
+    /// returns the errors among them. Failed models stay empty.
+    pub fn update(&mut self) -> Vec<AssetError> {
+        let mut finished = std::mem::take(&mut self.loaded_inline);
+        if let Some(workers) = &self.workers {
+            finished.extend(workers.finished());
+        }
+
+        finished
+            .into_iter()
+            .filter_map(|(id, loaded)| self.finish(id, loaded))
+            .collect()
+    }
+
+    /// Blocks until every background load has finished, for tools and tests
+    /// that have nothing to show meanwhile.
+    pub fn wait(&mut self) -> Vec<AssetError> {
+        let mut errors = self.update();
+
+        while !self.progress.is_done() {
+            let finished = self.workers.as_ref().and_then(WorkerPool::wait);
+            let (id, loaded) = match finished {
+                Some(finished) => finished,
+                None => break,
+            };
+            errors.extend(self.finish(id, loaded));
+        }
+
+        errors
+    }
+
+    fn finish(&mut self, id: usize, loaded: JobResult<Loaded>) -> Option<AssetError> {
+        let (path, request) = self.requests.remove(&id)?;
+        self.progress.finished += 1;
+
+        let loaded = match loaded {
+            Ok(loaded) => loaded,
+            Err(message) => {
+                if let Request::Texture(handle, _) = request {
+                    let texture = texture::load_solid([255, 255, 255]);
+                    let size = texture.size_in_bytes();
+                    self.textures.replace(handle, texture, size);
+                }
+                return Some(AssetError::Panicked(path, message));
+            }
+        };
+
+        match (request, loaded) {
+            (Request::Model(handle), Loaded::Model(result)) => {
+                let model = match result {
+                    Ok(model) => model,
+                    Err(err) => return Some(AssetError::Model(err)),
+                };
+                let size = model_size(&model);
+                self.models.replace(handle, model, size);
+                None
+            }
+            (Request::Texture(handle, options), Loaded::Texture(path, result)) => {
+                let uploaded = result.and_then(|images| {
+                    texture::upload_images(&images, &options)
+                        .map_err(|kind| TextureError { file: path, kind })
+                });
+                let (texture, error) = match uploaded {
+                    Ok(texture) => (texture, None),
+                    Err(err) => (
+                        texture::load_solid([255, 255, 255]),
+                        Some(AssetError::Texture(err)),
+                    ),
+                };
+                let size = texture.size_in_bytes();
+                self.textures.replace(handle, texture, size);
+                error
+            }
+            _ => unreachable!("background load came back as another kind of asset"),
+        }
+    }
+
+    pub fn progress(&self) -> Progress {
+        self.progress
+    }
+
+    /// A texture image, the same file loaded with different options is a
+    /// different texture.
+    pub fn load_texture(
+        &mut self,
+        path: &str,
+        options: &LoadOptions,
+    ) -> Result<Handle<Texture>, AssetError> {
+        let path = self.resolve(path);
+        let key = format!("{} {:?}", path, options);
+        if let Some(handle) = self.textures.acquire(&key) {
+            return Ok(handle);
+        }
+
+        let texture = texture::load_with(&path, options)?;
+        let size = texture.size_in_bytes();
+        Ok(self.textures.insert(key, path, texture, size))
+    }
+
+    /// A single pixel of `color`, shared by everything using that color.
+    pub fn solid_texture(&mut self, color: [u8; 3]) -> Handle<Texture> {
+        let key = format!("solid:#{:02x}{:02x}{:02x}", color[0], color[1], color[2]);
+        if let Some(handle) = self.textures.acquire(&key) {
+            return handle;
+        }
+
+        let texture = texture::load_solid(color);
+        let size = texture.size_in_bytes();
+        self.textures.insert(key.clone(), key, texture, size)
+    }
+
+    /// A program linked from a vertex and a fragment shader file.
+    pub fn load_shader(
+        &mut self,
+        vertex_path: &str,
+        fragment_path: &str,
+    ) -> Result<Handle<Shader>, AssetError> {
+        let (vertex_path, fragment_path) = (self.resolve(vertex_path), self.resolve(fragment_path));
+        let key = format!("{} + {}", vertex_path, fragment_path);
+        if let Some(handle) = self.shaders.acquire(&key) {
+            return Ok(handle);
+        }
+
+        // `shaders::load` can't report these itself
+        for path in &[&vertex_path, &fragment_path] {
+            fs::metadata(path).map_err(|err| AssetError::Io(path.to_string(), err))?;
+        }
+
+        let program = shaders::load(&vertex_path, &fragment_path);
+        Ok(self.shaders.insert(key.clone(), key, Shader { program }, 0))
+    }
+
+    pub fn model(&self, handle: Handle<Model>) -> &Model {
+        self.models.get(handle)
+    }
+
+    pub fn texture(&self, handle: Handle<Texture>) -> &Texture {
+        self.textures.get(handle)
+    }
+
+    pub fn shader(&self, handle: Handle<Shader>) -> &Shader {
+        self.shaders.get(handle)
+    }
+
+    pub fn release_model(&mut self, handle: Handle<Model>) -> bool {
+        self.models.release(handle)
+    }
+
+    pub fn release_texture(&mut self, handle: Handle<Texture>) -> bool {
+        self.textures.release(handle)
+    }
+
+    pub fn release_shader(&mut self, handle: Handle<Shader>) -> bool {
+        self.shaders.release(handle)
+    }
+
+    /// Every loaded asset, largest first.
+    pub fn usage(&self) -> Vec<AssetUsage> {
+        let mut usage: Vec<AssetUsage> = self
+            .models
+            .usage(AssetKind::Model)
+            .chain(self.textures.usage(AssetKind::Texture))
+            .chain(self.shaders.usage(AssetKind::Shader))
+            .collect();
+
+        usage.sort_by_key(|usage| Reverse(usage.size));
+        usage
+    }
+
+    pub fn total_size(&self) -> usize {
+        self.usage().iter().map(|usage| usage.size).sum()
+    }
+}
+
+fn model_size(model: &Model) -> usize {
+    model
+        .meshes
+        .iter()
+        .map(|sub_mesh| sub_mesh.mesh.size_in_bytes())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh;
+
+    #[test]
+    fn shares_and_frees_models() {
+        let directory = std::env::temp_dir().join("solar-system-asset-tests");
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(
+            directory.join("triangle.obj"),
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n",
+        )
+        .unwrap();
+
+        let mut assets = AssetManager::new(&directory);
+        let first = assets.load_model("triangle.obj").unwrap();
+        let second = assets.load_model("triangle.obj").unwrap();
+        let sphere = assets.model_with("sphere", || {
+            Model::from_mesh("Sphere", mesh::sphere(4, 8), None)
+        });
+        let same_sphere = assets.model_with("sphere", || unreachable!());
+
+        assert_eq!(first, second);
+        assert_eq!(sphere, same_sphere);
+        assert_eq!(assets.model(first).meshes[0].mesh.triangle_count(), 1);
+
+        let usage = assets.usage();
+        assert_eq!(usage.len(), 2);
+        assert_eq!(usage[0].path, "generated:sphere");
+        assert_eq!(usage[0].references, 2);
+        assert_eq!(usage[0].size, mesh::sphere(4, 8).size_in_bytes());
+        assert_eq!(usage[1].path, assets.resolve("triangle.obj"));
+
+        assert!(!assets.release_model(first));
+        assert!(assets.release_model(second));
+        assert_eq!(assets.usage().len(), 1);
+
+        // loading again after the last release reads the file anew
+        let third = assets.load_model("triangle.obj").unwrap();
+        assert_ne!(third, first);
+        assert!(matches!(
+            assets.load_model("missing.obj"),
+            Err(AssetError::Model(_))
+        ));
+    }
+
+    #[test]
+    fn loads_models_in_the_background() {
+        let directory = std::env::temp_dir().join("solar-system-asset-tests");
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(
+            directory.join("square.obj"),
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n",
+        )
+        .unwrap();
+
+        let mut assets = AssetManager::new(&directory);
+        assets.start_workers();
+        let square = assets.request_model("square.obj");
+        let missing = assets.request_model("missing.obj");
+        assert_eq!(assets.request_model("square.obj"), square);
+        assert_eq!(assets.progress().total, 2);
+
+        let errors = assets.wait();
+
+        assert!(assets.progress().is_done());
+        assert_eq!(assets.model(square).meshes[0].mesh.triangle_count(), 2);
+        assert!(assets.model(missing).meshes.is_empty());
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0], AssetError::Model(_)));
+    }
+}
@@ -0,0 +1,533 @@
+//! Binary mesh format, so models don't have to be parsed from text on every
+//! startup.
+//!
+//! Layout, all numbers little-endian:
+//!
+//! ```text
+//! header      magic "SSMB", version u32, material count u32, sub-mesh count u32
+//! layout      attribute count u8, then (semantic u8, f32 components u8) each
+//! materials   name, 4 colors, 2 scalars, 6 optional texture paths
+//! sub-meshes  name, material i32 (-1 for none), vertex count u32,
+//!             index size u8 (2 or 4), index count u32,
+//!             vertex blob, index blob
+//! ```
+//!
+//! Strings are a u32 byte length followed by UTF-8 bytes. Texture paths are
+//! stored relative to the baked file when they live next to it.
+
+use crate::material::Material;
+use crate::mesh::{Indices, Mesh, Vertex};
+use crate::model::{Model, SubMesh};
+use crate::object::{self, LoadOptions, ObjError};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub const MAGIC: [u8; 4] = *b"SSMB";
+pub const VERSION: u32 = 3;
+
+/// Vertex attributes as (semantic, f32 components), in `Vertex` field order.
+const VERTEX_LAYOUT: [(u8, u8); 4] = [
+    (SEMANTIC_POSITION, 3),
+    (SEMANTIC_UV, 2),
+    (SEMANTIC_NORMAL, 3),
+    (SEMANTIC_TANGENT, 4),
+];
+
+/// Bytes of one vertex in a vertex blob.
+const VERTEX_SIZE: usize = (3 + 2 + 3 + 4) * 4;
+
+const SEMANTIC_POSITION: u8 = 0;
+const SEMANTIC_UV: u8 = 1;
+const SEMANTIC_NORMAL: u8 = 2;
+const SEMANTIC_TANGENT: u8 = 3;
+
+#[derive(Debug)]
+pub enum BakeError {
+    Io(io::Error),
+    Obj(ObjError),
+    BadMagic,
+    UnsupportedVersion(u32),
+    LayoutMismatch,
+    Truncated,
+    Corrupt(&'static str),
+}
+
+impl fmt::Display for BakeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BakeError::Io(err) => write!(f, "{}", err),
+            BakeError::Obj(err) => write!(f, "{}", err),
+            BakeError::BadMagic => write!(f, "not a baked mesh file"),
+            BakeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported version {} (expected {})", version, VERSION)
+            }
+            BakeError::LayoutMismatch => write!(f, "vertex layout doesn't match this build"),
+            BakeError::Truncated => write!(f, "file is truncated"),
+            BakeError::Corrupt(what) => write!(f, "corrupt file: {}", what),
+        }
+    }
+}
+
+impl Error for BakeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BakeError::Io(err) => Some(err),
+            BakeError::Obj(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for BakeError {
+    fn from(err: io::Error) -> BakeError {
+        BakeError::Io(err)
+    }
+}
+
+impl From<ObjError> for BakeError {
+    fn from(err: ObjError) -> BakeError {
+        BakeError::Obj(err)
+    }
+}
+
+/// Where the baked version of `source` lives: same path, `.mesh` extension.
+pub fn cache_path(source: &str) -> String {
+    Path::new(source)
+        .with_extension("mesh")
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Loads `source` from its baked file when that one is at least as new,
+/// otherwise (or if the baked file is stale or from another version) parses
+/// the OBJ itself with `options`. The baked file is used as `objbake` made
+/// it, whatever the options.
+pub fn load_cached(source: &str, options: &LoadOptions) -> Result<Model, BakeError> {
+    let cache = cache_path(source);
+
+    if is_fresh(&cache, source) {
+        if let Ok(model) = load(&cache) {
+            return Ok(model);
+        }
+    }
+
+    Ok(object::load_with(source, options)?)
+}
+
+fn is_fresh(cache: &str, source: &str) -> bool {
+    let modified = |path: &str| fs::metadata(path).and_then(|metadata| metadata.modified());
+
+    match (modified(cache), modified(source)) {
+        (Ok(cache), Ok(source)) => cache >= source,
+        // without a source there is nothing to be stale against
+        (Ok(_), Err(_)) => true,
+        _ => false,
+    }
+}
+
+pub fn save(model: &Model, path: &str) -> Result<(), BakeError> {
+    let directory = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+    let mut bytes = vec![];
+
+    bytes.extend_from_slice(&MAGIC);
+    write_u32(&mut bytes, VERSION);
+    write_u32(&mut bytes, model.materials.len() as u32);
+    write_u32(&mut bytes, model.meshes.len() as u32);
+
+    bytes.push(VERTEX_LAYOUT.len() as u8);
+    for &(semantic, components) in &VERTEX_LAYOUT {
+        bytes.push(semantic);
+        bytes.push(components);
+    }
+
+    for material in &model.materials {
+        write_string(&mut bytes, &material.name);
+        for color in &[
+            material.ambient,
+            material.diffuse,
+            material.specular,
+            material.emissive,
+        ] {
+            write_f32s(&mut bytes, &[color.x, color.y, color.z]);
+        }
+        write_f32s(&mut bytes, &[material.shininess, material.dissolve]);
+
+        for map in &[
+            &material.diffuse_map,
+            &material.bump_map,
+            &material.normal_map,
+            &material.specular_map,
+            &material.emissive_map,
+            &material.night_map,
+        ] {
+            match map {
+                Some(map) => {
+                    bytes.push(1);
+                    let relative = Path::new(map).strip_prefix(directory);
+                    let map = relative.map_or_else(
+                        |_| map.clone(),
+                        |relative| relative.to_string_lossy().into_owned(),
+                    );
+                    write_string(&mut bytes, &map);
+                }
+                None => bytes.push(0),
+            }
+        }
+    }
+
+    for sub_mesh in &model.meshes {
+        let mesh = &sub_mesh.mesh;
+
+        write_string(&mut bytes, &sub_mesh.name);
+        write_u32(
+            &mut bytes,
+            sub_mesh.material.map_or(-1, |material| material as i32) as u32,
+        );
+        write_u32(&mut bytes, mesh.vertices.len() as u32);
+
+        match &mesh.indices {
+            Indices::U16(indices) => {
+                bytes.push(2);
+                write_u32(&mut bytes, indices.len() as u32);
+            }
+            Indices::U32(indices) => {
+                bytes.push(4);
+                write_u32(&mut bytes, indices.len() as u32);
+            }
+        }
+
+        for vertex in &mesh.vertices {
+            let (p, uv, n, t) = (vertex.position, vertex.uv, vertex.normal, vertex.tangent);
+            write_f32s(
+                &mut bytes,
+                &[p.x, p.y, p.z, uv.x, uv.y, n.x, n.y, n.z, t.x, t.y, t.z, t.w],
+            );
+        }
+
+        match &mesh.indices {
+            Indices::U16(indices) => {
+                for index in indices {
+                    bytes.extend_from_slice(&index.to_le_bytes());
+                }
+            }
+            Indices::U32(indices) => {
+                for index in indices {
+                    bytes.extend_from_slice(&index.to_le_bytes());
+                }
+            }
+        }
+    }
+
+    fs::write(path, bytes)?;
+
+    Ok(())
+}
+
+pub fn load(path: &str) -> Result<Model, BakeError> {
+    let bytes = fs::read(path)?;
+    let directory = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+
+    parse(&bytes, directory)
+}
+
+/// Reads a baked model from memory, relative texture paths are joined to
+/// `directory`.
+pub fn parse(bytes: &[u8], directory: &Path) -> Result<Model, BakeError> {
+    let mut reader = Reader { bytes, position: 0 };
+
+    if reader.take(4)? != MAGIC {
+        return Err(BakeError::BadMagic);
+    }
+
+    let version = reader.u32()?;
+    if version != VERSION {
+        return Err(BakeError::UnsupportedVersion(version));
+    }
+
+    let material_count = reader.u32()? as usize;
+    let mesh_count = reader.u32()? as usize;
+
+    let attribute_count = reader.u8()? as usize;
+    let mut layout = vec![];
+    for _ in 0..attribute_count {
+        layout.push((reader.u8()?, reader.u8()?));
+    }
+    if layout != VERTEX_LAYOUT {
+        return Err(BakeError::LayoutMismatch);
+    }
+
+    let mut materials = vec![];
+    for _ in 0..material_count {
+        let mut material = Material::new(&reader.string()?);
+
+        material.ambient = reader.vec3()?;
+        material.diffuse = reader.vec3()?;
+        material.specular = reader.vec3()?;
+        material.emissive = reader.vec3()?;
+        material.shininess = reader.f32()?;
+        material.dissolve = reader.f32()?;
+
+        for map in [
+            &mut material.diffuse_map,
+            &mut material.bump_map,
+            &mut material.normal_map,
+            &mut material.specular_map,
+            &mut material.emissive_map,
+            &mut material.night_map,
+        ] {
+            if reader.u8()? == 1 {
+                *map = Some(
+                    directory
+                        .join(reader.string()?)
+                        .to_string_lossy()
+                        .into_owned(),
+                );
+            }
+        }
+
+        materials.push(material);
+    }
+
+    let mut meshes = vec![];
+    for _ in 0..mesh_count {
+        let name = reader.string()?;
+        let material = match reader.u32()? as i32 {
+            -1 => None,
+            material if (material as usize) < materials.len() => Some(material as usize),
+            _ => return Err(BakeError::Corrupt("material index out of range")),
+        };
+        let vertex_count = reader.u32()? as usize;
+        let index_size = reader.u8()?;
+        let index_count = reader.u32()? as usize;
+
+        // before allocating for them, so a corrupt count can't ask for more
+        // memory than the file could hold
+        if vertex_count > reader.remaining() / VERTEX_SIZE {
+            return Err(BakeError::Truncated);
+        }
+
+        let mut vertices = Vec::with_capacity(vertex_count);
+        for _ in 0..vertex_count {
+            vertices.push(Vertex {
+                position: reader.vec3()?,
+                uv: glm::vec2(reader.f32()?, reader.f32()?),
+                normal: reader.vec3()?,
+                tangent: glm::vec4(reader.f32()?, reader.f32()?, reader.f32()?, reader.f32()?),
+            });
+        }
+
+        let indices = match index_size {
+            2 => Indices::U16(
+                reader
+                    .take(index_count * 2)?
+                    .chunks_exact(2)
+                    .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+                    .collect(),
+            ),
+            4 => Indices::U32(
+                reader
+                    .take(index_count * 4)?
+                    .chunks_exact(4)
+                    .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                    .collect(),
+            ),
+            _ => return Err(BakeError::Corrupt("index size must be 2 or 4")),
+        };
+
+        if indices.iter().any(|index| index >= vertex_count) {
+            return Err(BakeError::Corrupt("vertex index out of range"));
+        }
+
+        meshes.push(SubMesh {
+            name,
+            material,
+            mesh: Mesh { vertices, indices },
+            visible: true,
+        });
+    }
+
+    Ok(Model { meshes, materials })
+}
+
+fn write_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_f32s(bytes: &mut Vec<u8>, values: &[f32]) {
+    for value in values {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+fn write_string(bytes: &mut Vec<u8>, value: &str) {
+    write_u32(bytes, value.len() as u32);
+    bytes.extend_from_slice(value.as_bytes());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], BakeError> {
+        let end = self
+            .position
+            .checked_add(count)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or(BakeError::Truncated)?;
+
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+
+        Ok(bytes)
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.position
+    }
+
+    fn u8(&mut self) -> Result<u8, BakeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, BakeError> {
+        let bytes = self.take(4)?;
+
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn f32(&mut self) -> Result<f32, BakeError> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    fn vec3(&mut self) -> Result<glm::Vector3<f32>, BakeError> {
+        Ok(glm::vec3(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    fn string(&mut self) -> Result<String, BakeError> {
+        let length = self.u32()? as usize;
+
+        String::from_utf8(self.take(length)?.to_vec())
+            .map_err(|_| BakeError::Corrupt("string is not UTF-8"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh;
+
+    fn test_directory() -> std::path::PathBuf {
+        let directory = std::env::temp_dir().join("solar-system-bake-tests");
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    fn sample_model(directory: &Path) -> Model {
+        let mut material = Material::new("Moon");
+        material.diffuse = glm::vec3(0.5, 0.25, 1.0);
+        material.diffuse_map = Some(directory.join("moon.jpg").to_string_lossy().into_owned());
+        material.bump_map = Some("/elsewhere/bump.png".to_string());
+        material.night_map = Some(directory.join("lights.jpg").to_string_lossy().into_owned());
+
+        let mut model = Model::from_mesh("Moon", mesh::sphere(8, 16), Some(material));
+        model.meshes.push(SubMesh {
+            name: "Rock".to_string(),
+            material: None,
+            mesh: mesh::icosphere(1),
+            visible: true,
+        });
+
+        model
+    }
+
+    #[test]
+    fn round_trips_a_model() {
+        let directory = test_directory();
+        let path = directory.join("round_trip.mesh");
+        let path = path.to_str().unwrap();
+        let model = sample_model(&directory);
+
+        save(&model, path).unwrap();
+        let loaded = load(path).unwrap();
+
+        assert_eq!(loaded, model);
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let directory = test_directory();
+        let path = directory.join("version.mesh");
+        let path = path.to_str().unwrap();
+        save(&sample_model(&directory), path).unwrap();
+
+        let mut bytes = fs::read(path).unwrap();
+        bytes[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+
+        assert!(matches!(
+            parse(&bytes, &directory),
+            Err(BakeError::UnsupportedVersion(_))
+        ));
+        assert!(matches!(
+            parse(b"OBJ!\x01\x00\x00\x00", &directory),
+            Err(BakeError::BadMagic)
+        ));
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let directory = test_directory();
+        let path = directory.join("truncated.mesh");
+        let path = path.to_str().unwrap();
+        save(&sample_model(&directory), path).unwrap();
+
+        let bytes = fs::read(path).unwrap();
+
+        assert!(matches!(
+            parse(&bytes[..bytes.len() - 1], &directory),
+            Err(BakeError::Truncated)
+        ));
+
+        // a vertex count far beyond the file's size
+        let model = Model::from_mesh("Rock", mesh::icosphere(1), None);
+        save(&model, path).unwrap();
+        let mut bytes = fs::read(path).unwrap();
+        let vertex_count = 16 + 1 + 2 * VERTEX_LAYOUT.len() + 4 + "Rock".len() + 4;
+        bytes[vertex_count..vertex_count + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        assert!(matches!(
+            parse(&bytes, &directory),
+            Err(BakeError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn prefers_a_fresh_cache() {
+        let directory = test_directory();
+        let source = directory.join("cached.obj");
+        let source = source.to_str().unwrap();
+
+        fs::write(source, "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        assert_eq!(
+            load_cached(source, &LoadOptions::default()).unwrap().meshes[0]
+                .mesh
+                .vertices
+                .len(),
+            3
+        );
+
+        // a newer baked file wins over the source
+        save(&sample_model(&directory), &cache_path(source)).unwrap();
+        assert_eq!(
+            load_cached(source, &LoadOptions::default())
+                .unwrap()
+                .meshes
+                .len(),
+            2
+        );
+    }
+}
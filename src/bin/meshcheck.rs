@@ -0,0 +1,79 @@
+//! Validates model files and prints statistics for each part:
+//!
+//! ```text
+//! meshcheck resources/objects/ship.obj [more.obj...]
+//! meshcheck --strict resources/objects/*.obj
+//! ```
+//!
+//! Exits with 1 if any file fails to load or has errors (or warnings, with
+//! `--strict`), so it can gate asset changes.
+
+use solar_system::check::{self, Severity};
+use solar_system::import;
+use std::env;
+use std::process;
+
+fn main() {
+    let mut strict = false;
+    let mut sources = vec![];
+
+    for argument in env::args().skip(1) {
+        match argument.as_str() {
+            "--strict" => strict = true,
+            _ => sources.push(argument),
+        }
+    }
+
+    if sources.is_empty() {
+        eprintln!("usage: meshcheck [--strict] <model>...");
+        process::exit(2);
+    }
+
+    let failing = if strict {
+        Severity::Warning
+    } else {
+        Severity::Error
+    };
+    let mut failed = false;
+
+    for source in &sources {
+        let model = match import::load(source) {
+            Ok(model) => model,
+            Err(err) => {
+                eprintln!("{}", err);
+                failed = true;
+                continue;
+            }
+        };
+
+        println!("{}", source);
+
+        for sub_mesh in &model.meshes {
+            let report = check::check(&sub_mesh.mesh);
+
+            print!(
+                "  {}: {} vertices, {} triangles",
+                sub_mesh.name, report.vertices, report.triangles
+            );
+            match report.bounds {
+                Some(aabb) => println!(
+                    ", bounds ({}, {}, {}) .. ({}, {}, {})",
+                    aabb.min.x, aabb.min.y, aabb.min.z, aabb.max.x, aabb.max.y, aabb.max.z
+                ),
+                None => println!(),
+            }
+
+            for issue in report.issues() {
+                println!("    {}", issue);
+            }
+
+            if report.worst().is_some_and(|worst| worst >= failing) {
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,68 @@
+//! Converts OBJ files into the baked mesh format, next to the source:
+//!
+//! ```text
+//! objbake resources/objects/ship.obj [more.obj...]
+//! objbake resources/objects/ship.obj -o ship.mesh
+//! ```
+
+use solar_system::bake;
+use solar_system::object;
+use std::env;
+use std::process;
+
+fn main() {
+    let mut arguments = env::args().skip(1);
+    let mut sources = vec![];
+    let mut output = None;
+
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "-o" => output = arguments.next(),
+            _ => sources.push(argument),
+        }
+    }
+
+    if sources.is_empty() || (output.is_some() && sources.len() > 1) {
+        eprintln!("usage: objbake <file.obj>... | objbake <file.obj> -o <file.mesh>");
+        process::exit(2);
+    }
+
+    let mut failed = false;
+
+    for source in &sources {
+        let target = output.clone().unwrap_or_else(|| bake::cache_path(source));
+
+        let result = object::load(source)
+            .map_err(bake::BakeError::from)
+            .and_then(|model| bake::save(&model, &target).map(|_| model));
+
+        match result {
+            Ok(model) => {
+                let vertices: usize = model.meshes.iter().map(|m| m.mesh.vertices.len()).sum();
+                let triangles: usize = model.meshes.iter().map(|m| m.mesh.triangle_count()).sum();
+
+                println!(
+                    "{} -> {} ({} parts, {} vertices, {} triangles)",
+                    source,
+                    target,
+                    model.meshes.len(),
+                    vertices,
+                    triangles
+                );
+            }
+            // OBJ errors already name the file and line
+            Err(bake::BakeError::Obj(err)) => {
+                eprintln!("{}", err);
+                failed = true;
+            }
+            Err(err) => {
+                eprintln!("{}: {}", target, err);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
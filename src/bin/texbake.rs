@@ -0,0 +1,116 @@
+//! Bakes images into the texture format `texture::load` reads directly, with
+//! their mip chains made up front, next to the source unless told otherwise.
+//! Runs on the CPU alone, no window or GL context needed:
+//!
+//! ```text
+//! texbake resources/textures/2k_moon.jpg [more.jpg...]
+//! texbake resources/textures/normals.png --linear -o normals.tex
+//! texbake resources/textures/2k_stars.jpg --cubemap 1024
+//! ```
+//!
+//! `--cubemap <size>` reprojects equirectangular panoramas into six faces of
+//! that size, `--linear` is for data such as normal maps whose mipmaps
+//! shouldn't be averaged as sRGB colors, and `--no-mips` keeps only the full
+//! size image.
+
+use solar_system::cubemap;
+use solar_system::texture::{self, TextureImages};
+use solar_system::texture_bake;
+use std::env;
+use std::process;
+
+const USAGE: &str = "\
+usage: texbake [--cubemap <size>] [--linear] [--no-mips] <image>...
+       texbake [--cubemap <size>] [--linear] [--no-mips] <image> -o <file.tex>";
+
+fn main() {
+    let mut arguments = env::args().skip(1);
+    let mut sources = vec![];
+    let mut output = None;
+    let mut cubemap_size = None;
+    let mut linear = false;
+    let mut mipmaps = true;
+
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "-o" => output = arguments.next(),
+            "--cubemap" => match arguments.next().and_then(|size| size.parse::<u32>().ok()) {
+                Some(size) if size > 0 => cubemap_size = Some(size),
+                _ => usage(),
+            },
+            "--linear" => linear = true,
+            "--no-mips" => mipmaps = false,
+            _ => sources.push(argument),
+        }
+    }
+
+    if sources.is_empty() || (output.is_some() && sources.len() > 1) {
+        usage();
+    }
+
+    let mut failed = false;
+
+    for source in &sources {
+        let target = output
+            .clone()
+            .unwrap_or_else(|| texture_bake::cache_path(source));
+
+        let image = match texture::decode(source) {
+            Ok(image) => image,
+            // decode errors already name the file
+            Err(err) => {
+                eprintln!("{}", err);
+                failed = true;
+                continue;
+            }
+        };
+
+        let faces = match cubemap_size {
+            Some(size) => cubemap::from_equirect(&image, size).to_vec(),
+            None => vec![image],
+        };
+        let srgb = !linear && !faces[0].pixels.is_float();
+        let images = TextureImages {
+            faces: faces
+                .into_iter()
+                .map(|face| {
+                    if mipmaps {
+                        texture_bake::mip_chain(&face, srgb)
+                    } else {
+                        vec![face]
+                    }
+                })
+                .collect(),
+            srgb,
+        };
+
+        match texture_bake::save(&images, &target) {
+            Ok(()) => {
+                let first = &images.faces[0][0];
+                println!(
+                    "{} -> {} ({}x{}{}, {} levels, {})",
+                    source,
+                    target,
+                    first.width,
+                    first.height,
+                    if images.is_cubemap() { " cubemap" } else { "" },
+                    images.faces[0].len(),
+                    if srgb { "sRGB" } else { "linear" }
+                );
+            }
+            Err(err) => {
+                eprintln!("{}: {}", target, err);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
@@ -0,0 +1,291 @@
+//! Bounding volumes and view frustum tests, so draws that can't be seen are
+//! skipped.
+
+/// Axis-aligned box, `min` and `max` are opposite corners.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: glm::Vector3<f32>,
+    pub max: glm::Vector3<f32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: glm::Vector3<f32>,
+    pub radius: f32,
+}
+
+/// Both volumes of a mesh: the sphere is the cheaper test, the box the
+/// tighter one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub aabb: Aabb,
+    pub sphere: BoundingSphere,
+}
+
+impl Aabb {
+    /// Box around `points`, or an empty one at the origin when there are none.
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a glm::Vector3<f32>>) -> Aabb {
+        let mut points = points.into_iter();
+        let first = match points.next() {
+            Some(&first) => first,
+            None => {
+                let origin = glm::vec3(0.0, 0.0, 0.0);
+                return Aabb {
+                    min: origin,
+                    max: origin,
+                };
+            }
+        };
+
+        points.fold(
+            Aabb {
+                min: first,
+                max: first,
+            },
+            |aabb, &p| Aabb {
+                min: glm::min(aabb.min, p),
+                max: glm::max(aabb.max, p),
+            },
+        )
+    }
+
+    pub fn center(&self) -> glm::Vector3<f32> {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Smallest axis-aligned box around this one after `matrix` moved it.
+    pub fn transform(&self, matrix: &glm::Matrix4<f32>) -> Aabb {
+        let translation = matrix.c3.truncate(3);
+        let (mut min, mut max) = (translation, translation);
+
+        // each output axis picks, per input axis, whichever extreme lands
+        // lower (or higher) after scaling
+        for column in 0..3 {
+            for row in 0..3 {
+                let a = matrix[column][row] * self.min[column];
+                let b = matrix[column][row] * self.max[column];
+
+                min[row] += a.min(b);
+                max[row] += a.max(b);
+            }
+        }
+
+        Aabb { min, max }
+    }
+}
+
+impl BoundingSphere {
+    pub fn transform(&self, matrix: &glm::Matrix4<f32>) -> BoundingSphere {
+        let center = *matrix * self.center.extend(1.0);
+        // a non-uniform scale stretches the sphere by its largest factor
+        let scale = [matrix.c0, matrix.c1, matrix.c2]
+            .iter()
+            .map(|column| glm::length(column.truncate(3)))
+            .fold(0.0, f32::max);
+
+        BoundingSphere {
+            center: center.truncate(3),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+impl Bounds {
+    /// Volumes around `points`, the sphere is centered on the box.
+    pub fn from_points<'a>(points: impl Iterator<Item = &'a glm::Vector3<f32>> + Clone) -> Bounds {
+        let aabb = Aabb::from_points(points.clone());
+        let center = aabb.center();
+        let radius = points.map(|&p| glm::length(p - center)).fold(0.0, f32::max);
+
+        Bounds {
+            aabb,
+            sphere: BoundingSphere { center, radius },
+        }
+    }
+
+    pub fn transform(&self, matrix: &glm::Matrix4<f32>) -> Bounds {
+        Bounds {
+            aabb: self.aabb.transform(matrix),
+            sphere: self.sphere.transform(matrix),
+        }
+    }
+}
+
+/// The six planes of a view-projection matrix's clip volume, normals
+/// pointing inwards.
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    planes: [glm::Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes from `view_projection` (Gribb & Hartmann), objects
+    /// are then tested in world space.
+    pub fn from_matrix(view_projection: &glm::Matrix4<f32>) -> Frustum {
+        let m = view_projection;
+        let row = |i: usize| glm::vec4(m[0][i], m[1][i], m[2][i], m[3][i]);
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+
+        let mut planes = [w + x, w - x, w + y, w - y, w + z, w - z];
+        for plane in planes.iter_mut() {
+            *plane = *plane / glm::length(plane.truncate(3));
+        }
+
+        Frustum { planes }
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| glm::dot(plane.truncate(3), sphere.center) + plane.w >= -sphere.radius)
+    }
+
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // the corner furthest along the plane's normal
+            let corner = glm::vec3(
+                if plane.x >= 0.0 {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if plane.y >= 0.0 {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if plane.z >= 0.0 {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+            );
+
+            glm::dot(plane.truncate(3), corner) + plane.w >= 0.0
+        })
+    }
+
+    /// Sphere first since it is cheaper, then the box for what it lets
+    /// through.
+    pub fn intersects(&self, bounds: &Bounds) -> bool {
+        self.intersects_sphere(&bounds.sphere) && self.intersects_aabb(&bounds.aabb)
+    }
+}
+
+/// How many draws made it through culling, for debugging.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CullStats {
+    pub drawn: usize,
+    pub culled: usize,
+}
+
+/// Frustum test for one frame, counting what passes and what doesn't.
+pub struct Culler {
+    pub frustum: Frustum,
+    pub stats: CullStats,
+}
+
+impl Culler {
+    pub fn new(view_projection: &glm::Matrix4<f32>) -> Culler {
+        Culler {
+            frustum: Frustum::from_matrix(view_projection),
+            stats: CullStats::default(),
+        }
+    }
+
+    /// Whether `bounds`, placed in the world by `model_matrix`, can be seen.
+    pub fn is_visible(&mut self, bounds: &Bounds, model_matrix: &glm::Matrix4<f32>) -> bool {
+        let visible = self.frustum.intersects(&bounds.transform(model_matrix));
+
+        if visible {
+            self.stats.drawn += 1;
+        } else {
+            self.stats.culled += 1;
+        }
+
+        visible
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh;
+
+    fn identity() -> glm::Matrix4<f32> {
+        glm::mat4(
+            1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+        )
+    }
+
+    fn camera() -> Frustum {
+        // at +6 on Z looking at the origin, like the scene's start
+        let projection = glm::ext::perspective(glm::radians(45.0), 16.0 / 9.0, 0.1, 100.0);
+        let view = glm::ext::look_at(
+            glm::vec3(0.0, 0.0, 6.0),
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(0.0, 1.0, 0.0),
+        );
+
+        Frustum::from_matrix(&(projection * view))
+    }
+
+    #[test]
+    fn sphere_mesh_bounds() {
+        let bounds = mesh::sphere(16, 32).bounds();
+
+        assert!(glm::length(bounds.aabb.min - glm::vec3(-1.0, -1.0, -1.0)) < 1e-5);
+        assert!(glm::length(bounds.aabb.max - glm::vec3(1.0, 1.0, 1.0)) < 1e-5);
+        assert!(glm::length(bounds.sphere.center) < 1e-5);
+        assert!((bounds.sphere.radius - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn transforms_bounds() {
+        let bounds = mesh::sphere(16, 32).bounds();
+        let matrix = glm::ext::translate(&identity(), glm::vec3(4.0, 0.0, 0.0));
+        let matrix = glm::ext::rotate(&matrix, glm::radians(45.0), glm::vec3(0.0, 1.0, 0.0));
+        let matrix = glm::ext::scale(&matrix, glm::vec3(0.5, 2.0, 0.5));
+
+        let moved = bounds.transform(&matrix);
+
+        assert!(glm::length(moved.sphere.center - glm::vec3(4.0, 0.0, 0.0)) < 1e-5);
+        assert!((moved.sphere.radius - 2.0).abs() < 1e-5);
+        // the rotated square footprint grows to its diagonal
+        let half = 0.5 * 2f32.sqrt();
+        assert!(glm::length(moved.aabb.min - glm::vec3(4.0 - half, -2.0, -half)) < 1e-5);
+        assert!(glm::length(moved.aabb.max - glm::vec3(4.0 + half, 2.0, half)) < 1e-5);
+    }
+
+    #[test]
+    fn culls_outside_the_frustum() {
+        let frustum = camera();
+        let unit = mesh::sphere(8, 16).bounds();
+        let at = |x, y, z| glm::ext::translate(&identity(), glm::vec3(x, y, z));
+
+        assert!(frustum.intersects(&unit.transform(&at(0.0, 0.0, 0.0))));
+        // behind the camera
+        assert!(!frustum.intersects(&unit.transform(&at(0.0, 0.0, 10.0))));
+        // far off to the side
+        assert!(!frustum.intersects(&unit.transform(&at(30.0, 0.0, 0.0))));
+        // beyond the far plane
+        assert!(!frustum.intersects(&unit.transform(&at(0.0, 0.0, -200.0))));
+        // poking in from the left edge
+        assert!(frustum.intersects(&unit.transform(&at(-4.5, 0.0, 0.0))));
+
+        let mut culler = Culler {
+            frustum,
+            stats: CullStats::default(),
+        };
+        culler.is_visible(&unit, &at(0.0, 0.0, 0.0));
+        culler.is_visible(&unit, &at(0.0, 0.0, 10.0));
+        culler.is_visible(&unit, &at(1.0, 0.0, 0.0));
+        assert_eq!(
+            culler.stats,
+            CullStats {
+                drawn: 2,
+                culled: 1
+            }
+        );
+    }
+}
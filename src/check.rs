@@ -0,0 +1,305 @@
+//! Mesh validation: counts what would break rendering (errors) or look wrong
+//! (warnings), for `meshcheck` and anything else that wants to vet assets
+//! before they reach the GPU.
+
+use crate::bounds::Aabb;
+use crate::mesh::{self, Mesh};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Triangles whose height is below this fraction of their longest edge count
+/// as zero area.
+const SLIVER_RATIO: f32 = 1e-6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Unusual but renders, e.g. UV seams.
+    Note,
+    /// Renders, but probably not as intended.
+    Warning,
+    /// Out of range or non-finite data that can crash or poison a draw.
+    Error,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Issue {
+    pub severity: Severity,
+    pub count: usize,
+    pub what: &'static str,
+}
+
+/// Findings for one mesh, each count is of the thing it is named after.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    pub vertices: usize,
+    pub triangles: usize,
+    /// `None` when there are no vertices.
+    pub bounds: Option<Aabb>,
+    pub out_of_range_indices: usize,
+    pub non_finite_vertices: usize,
+    /// Triangles using the same vertex or position twice.
+    pub degenerate_triangles: usize,
+    pub zero_area_triangles: usize,
+    /// Edges shared by more than two triangles.
+    pub non_manifold_edges: usize,
+    /// Triangles facing away from their vertex normals.
+    pub flipped_triangles: usize,
+    /// Edges between two triangles whose UVs disagree along it.
+    pub uv_seam_edges: usize,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Note => write!(f, "note"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} {}", self.severity, self.count, self.what)
+    }
+}
+
+impl Report {
+    /// Everything found, most severe first.
+    pub fn issues(&self) -> Vec<Issue> {
+        let mut issues: Vec<Issue> = [
+            (
+                Severity::Error,
+                self.out_of_range_indices,
+                "out-of-range indices",
+            ),
+            (
+                Severity::Error,
+                self.non_finite_vertices,
+                "vertices with NaN or infinite values",
+            ),
+            (
+                Severity::Warning,
+                self.degenerate_triangles,
+                "degenerate triangles",
+            ),
+            (
+                Severity::Warning,
+                self.zero_area_triangles,
+                "zero-area triangles",
+            ),
+            (
+                Severity::Warning,
+                self.non_manifold_edges,
+                "non-manifold edges",
+            ),
+            (
+                Severity::Warning,
+                self.flipped_triangles,
+                "triangles wound against their normals",
+            ),
+            (Severity::Note, self.uv_seam_edges, "UV seam edges"),
+        ]
+        .iter()
+        .filter(|(_, count, _)| *count > 0)
+        .map(|&(severity, count, what)| Issue {
+            severity,
+            count,
+            what,
+        })
+        .collect();
+
+        issues.sort_by_key(|issue| Reverse(issue.severity));
+        issues
+    }
+
+    pub fn worst(&self) -> Option<Severity> {
+        self.issues().iter().map(|issue| issue.severity).max()
+    }
+}
+
+/// Checks `mesh`, triangles with out-of-range indices are left out of every
+/// other test.
+pub fn check(mesh: &Mesh) -> Report {
+    let mut report = Report {
+        vertices: mesh.vertices.len(),
+        triangles: mesh.triangle_count(),
+        bounds: if mesh.vertices.is_empty() {
+            None
+        } else {
+            Some(mesh.bounds().aabb)
+        },
+        ..Report::default()
+    };
+
+    report.non_finite_vertices = mesh
+        .vertices
+        .iter()
+        .filter(|vertex| {
+            let (p, uv, n, t) = (vertex.position, vertex.uv, vertex.normal, vertex.tangent);
+            let finite = |values: &[f32]| values.iter().all(|value| value.is_finite());
+
+            !(finite(&[p.x, p.y, p.z])
+                && finite(&[uv.x, uv.y])
+                && finite(&[n.x, n.y, n.z])
+                && finite(&[t.x, t.y, t.z, t.w]))
+        })
+        .count();
+
+    // positions are welded, so edges are shared across UV and normal splits
+    let mut welded = HashMap::new();
+    let position_ids: Vec<usize> = mesh
+        .vertices
+        .iter()
+        .map(|vertex| {
+            let next = welded.len();
+            *welded
+                .entry(mesh::weld_key(vertex.position))
+                .or_insert(next)
+        })
+        .collect();
+
+    // per undirected edge: the vertices it runs between in each triangle
+    let mut edges: HashMap<(usize, usize), Vec<(usize, usize)>> = HashMap::new();
+
+    for triangle in 0..mesh.triangle_count() {
+        let corners = [0, 1, 2].map(|i| mesh.indices.get(triangle * 3 + i));
+        let out_of_range = corners
+            .iter()
+            .filter(|&&index| index >= mesh.vertices.len())
+            .count();
+        if out_of_range > 0 {
+            report.out_of_range_indices += out_of_range;
+            continue;
+        }
+
+        let positions = corners.map(|index| position_ids[index]);
+        if positions[0] == positions[1]
+            || positions[1] == positions[2]
+            || positions[2] == positions[0]
+        {
+            report.degenerate_triangles += 1;
+            continue;
+        }
+
+        let [a, b, c] = corners.map(|index| mesh.vertices[index]);
+        let face = glm::cross(b.position - a.position, c.position - a.position);
+        let longest = [
+            b.position - a.position,
+            c.position - b.position,
+            a.position - c.position,
+        ]
+        .iter()
+        .map(|&edge| glm::dot(edge, edge))
+        .fold(0.0, f32::max);
+        // |face| is twice the area, i.e. the height times the longest edge
+        if glm::length(face) <= SLIVER_RATIO * longest {
+            report.zero_area_triangles += 1;
+        } else if glm::dot(face, a.normal + b.normal + c.normal) < 0.0 {
+            report.flipped_triangles += 1;
+        }
+
+        for i in 0..3 {
+            let (from, to) = (corners[i], corners[(i + 1) % 3]);
+            let key = (
+                position_ids[from].min(position_ids[to]),
+                position_ids[from].max(position_ids[to]),
+            );
+            // store the ends in key order so both sides of an edge compare
+            let ends = if position_ids[from] < position_ids[to] {
+                (from, to)
+            } else {
+                (to, from)
+            };
+            edges.entry(key).or_default().push(ends);
+        }
+    }
+
+    for sides in edges.values() {
+        match sides.as_slice() {
+            [_, _, _, ..] => report.non_manifold_edges += 1,
+            [(a0, a1), (b0, b1)] => {
+                let uv = |index: usize| mesh.vertices[index].uv;
+                if uv(*a0) != uv(*b0) || uv(*a1) != uv(*b1) {
+                    report.uv_seam_edges += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{Indices, Vertex};
+
+    fn mesh(positions: &[[f32; 3]], indices: Vec<u32>) -> Mesh {
+        let vertices = positions
+            .iter()
+            .map(|&[x, y, z]| Vertex {
+                position: glm::vec3(x, y, z),
+                uv: glm::vec2(x, y),
+                normal: glm::vec3(0.0, 0.0, 1.0),
+                tangent: glm::vec4(1.0, 0.0, 0.0, 1.0),
+            })
+            .collect::<Vec<_>>();
+
+        Mesh {
+            indices: Indices::new(indices, vertices.len()),
+            vertices,
+        }
+    }
+
+    #[test]
+    fn sphere_is_clean_apart_from_its_seam() {
+        let report = check(&mesh::sphere(8, 16));
+
+        assert_eq!(report.triangles, 8 * 16 * 2 - 2 * 16);
+        assert_eq!(report.worst(), Some(Severity::Note));
+        // the meridian at u = 0/1 between the poles, plus every edge
+        // around the poles since each pole triangle has its own pole vertex
+        assert_eq!(report.uv_seam_edges, (8 - 2) + 2 * 16);
+    }
+
+    #[test]
+    fn finds_broken_triangles() {
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [2.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [0.0, 0.0, -1.0],
+            [1.0, 0.0, 0.0],
+        ];
+        #[rustfmt::skip]
+        let indices = vec![
+            0, 1, 2, // fine
+            0, 2, 1, // flipped
+            0, 1, 3, // zero area, on a line
+            0, 1, 6, // degenerate, 6 is at 1's position
+            0, 1, 4, // with the two before, a third face on edge 0-1
+            0, 1, 9, // out of range
+        ];
+        let mut mesh = mesh(&positions, indices);
+        mesh.vertices[5].normal.x = f32::NAN;
+
+        let report = check(&mesh);
+
+        assert_eq!(report.out_of_range_indices, 1);
+        assert_eq!(report.non_finite_vertices, 1);
+        assert_eq!(report.degenerate_triangles, 1);
+        assert_eq!(report.zero_area_triangles, 1);
+        assert_eq!(report.flipped_triangles, 1);
+        assert_eq!(report.non_manifold_edges, 1);
+        assert_eq!(report.worst(), Some(Severity::Error));
+        assert_eq!(
+            report.issues()[0].to_string(),
+            "error: 1 out-of-range indices"
+        );
+    }
+}
@@ -0,0 +1,712 @@
+//! Block-compressed (BC) textures as read from DDS and KTX2 files: the
+//! formats, whether the driver takes them, and decompression on the CPU for
+//! when it doesn't.
+//!
+//! Every format stores 4x4 texel blocks, partial blocks at the right and
+//! bottom edges included, rows of blocks from the top.
+
+use crate::extensions;
+use crate::texture::{Image, Pixels};
+use gl::types::GLenum;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+// from EXT_texture_compression_s3tc and EXT_texture_sRGB, which the gl
+// crate isn't generated with
+const COMPRESSED_RGB_S3TC_DXT1: GLenum = 0x83F0;
+const COMPRESSED_RGBA_S3TC_DXT1: GLenum = 0x83F1;
+const COMPRESSED_RGBA_S3TC_DXT5: GLenum = 0x83F3;
+const COMPRESSED_SRGB_S3TC_DXT1: GLenum = 0x8C4C;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT1: GLenum = 0x8C4D;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT5: GLenum = 0x8C4F;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockFormat {
+    /// DXT1, RGB.
+    Bc1,
+    /// DXT1 with 1-bit alpha.
+    Bc1Alpha,
+    /// DXT5, RGB and interpolated alpha.
+    Bc3,
+    /// Two BC4 channels, red and green, e.g. for normal maps.
+    Bc5,
+    /// BPTC, RGBA in one of eight modes per block.
+    Bc7,
+}
+
+impl BlockFormat {
+    pub fn block_size(self) -> usize {
+        match self {
+            BlockFormat::Bc1 | BlockFormat::Bc1Alpha => 8,
+            _ => 16,
+        }
+    }
+
+    /// Channels once decompressed. BC5 comes out with a zero blue channel,
+    /// the way GL samples it.
+    pub fn channels(self) -> u32 {
+        match self {
+            BlockFormat::Bc1 | BlockFormat::Bc5 => 3,
+            _ => 4,
+        }
+    }
+
+    /// Bytes of an image of `width` by `height` texels.
+    pub fn image_size(self, width: u32, height: u32) -> usize {
+        let blocks = |texels: u32| texels.div_ceil(4) as usize;
+
+        blocks(width) * blocks(height) * self.block_size()
+    }
+
+    /// The internal format, decoding colors from sRGB if `srgb` is set.
+    /// BC5 holds data rather than colors, and is always linear.
+    pub fn gl_internal_format(self, srgb: bool) -> GLenum {
+        match (self, srgb) {
+            (BlockFormat::Bc1, false) => COMPRESSED_RGB_S3TC_DXT1,
+            (BlockFormat::Bc1, true) => COMPRESSED_SRGB_S3TC_DXT1,
+            (BlockFormat::Bc1Alpha, false) => COMPRESSED_RGBA_S3TC_DXT1,
+            (BlockFormat::Bc1Alpha, true) => COMPRESSED_SRGB_ALPHA_S3TC_DXT1,
+            (BlockFormat::Bc3, false) => COMPRESSED_RGBA_S3TC_DXT5,
+            (BlockFormat::Bc3, true) => COMPRESSED_SRGB_ALPHA_S3TC_DXT5,
+            (BlockFormat::Bc5, _) => gl::COMPRESSED_RG_RGTC2,
+            (BlockFormat::Bc7, false) => gl::COMPRESSED_RGBA_BPTC_UNORM,
+            (BlockFormat::Bc7, true) => gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM,
+        }
+    }
+
+    /// Whether the driver can sample this format directly, as sRGB if
+    /// `srgb` is set. BC1 and BC3 are only ever an extension, and their sRGB
+    /// forms another one, BC5 is core since GL 3.0 and BC7 since 4.2.
+    pub fn is_supported(self, srgb: bool) -> bool {
+        match self {
+            BlockFormat::Bc1 | BlockFormat::Bc1Alpha | BlockFormat::Bc3 => {
+                extensions::has_any(&["GL_EXT_texture_compression_s3tc"])
+                    && (!srgb
+                        || extensions::has_any(&[
+                            "GL_EXT_texture_sRGB",
+                            "GL_EXT_texture_compression_s3tc_srgb",
+                        ]))
+            }
+            BlockFormat::Bc5 => true,
+            BlockFormat::Bc7 => {
+                extensions::version() >= (4, 2)
+                    || extensions::has_any(&["GL_ARB_texture_compression_bptc"])
+            }
+        }
+    }
+}
+
+/// Bits of a compressed texture format's texel, for memory estimates.
+pub(crate) fn bits_per_texel(internal_format: GLenum) -> Option<usize> {
+    match internal_format {
+        COMPRESSED_RGB_S3TC_DXT1
+        | COMPRESSED_RGBA_S3TC_DXT1
+        | COMPRESSED_SRGB_S3TC_DXT1
+        | COMPRESSED_SRGB_ALPHA_S3TC_DXT1 => Some(4),
+        COMPRESSED_RGBA_S3TC_DXT5
+        | COMPRESSED_SRGB_ALPHA_S3TC_DXT5
+        | gl::COMPRESSED_RG_RGTC2
+        | gl::COMPRESSED_RGBA_BPTC_UNORM
+        | gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM => Some(8),
+        _ => None,
+    }
+}
+
+/// Why a DDS or KTX2 file couldn't be read.
+#[derive(Debug)]
+pub enum ContainerError {
+    Io(io::Error),
+    BadMagic,
+    Truncated,
+    /// A valid file, but not one of the formats or layouts we load.
+    Unsupported(String),
+    Corrupt(&'static str),
+}
+
+impl fmt::Display for ContainerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ContainerError::Io(err) => write!(f, "{}", err),
+            ContainerError::BadMagic => write!(f, "not a DDS or KTX2 file"),
+            ContainerError::Truncated => write!(f, "file is truncated"),
+            ContainerError::Unsupported(what) => write!(f, "unsupported {}", what),
+            ContainerError::Corrupt(what) => write!(f, "corrupt file: {}", what),
+        }
+    }
+}
+
+impl Error for ContainerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ContainerError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ContainerError {
+    fn from(err: io::Error) -> ContainerError {
+        ContainerError::Io(err)
+    }
+}
+
+/// The little-endian `u32` at `offset` of a container's header.
+pub(crate) fn u32_at(bytes: &[u8], offset: usize) -> Result<u32, ContainerError> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or(ContainerError::Truncated)
+}
+
+/// Mip levels of `width` by `height` block-compressed images, halving down
+/// to 1x1, sliced off the front of `data`, which is advanced past them.
+pub(crate) fn take_levels(
+    format: BlockFormat,
+    width: u32,
+    height: u32,
+    levels: usize,
+    data: &mut &[u8],
+) -> Result<Vec<Image>, ContainerError> {
+    (0..levels)
+        .map(|level| {
+            let (width, height) = level_size(width, height, level);
+            let size = format.image_size(width, height);
+            if data.len() < size {
+                return Err(ContainerError::Truncated);
+            }
+
+            let (image, rest) = data.split_at(size);
+            *data = rest;
+
+            Ok(Image {
+                width,
+                height,
+                channels: format.channels(),
+                pixels: Pixels::Compressed(format, image.to_vec()),
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn level_size(width: u32, height: u32, level: usize) -> (u32, u32) {
+    let halve = |size: u32| size.checked_shr(level as u32).unwrap_or(0).max(1);
+
+    (halve(width), halve(height))
+}
+
+/// Levels in a full mip chain of a `width` by `height` image, the most a
+/// file can sensibly have. Counts from headers are checked against it
+/// before anything is allocated for them.
+pub(crate) fn max_levels(width: u32, height: u32) -> usize {
+    (32 - width.max(height).leading_zeros()) as usize
+}
+
+/// Decodes `data`, `width` by `height` texels of `format`, into 8-bit pixels
+/// with `format.channels()` channels.
+pub fn decompress(format: BlockFormat, width: u32, height: u32, data: &[u8]) -> Image {
+    let channels = format.channels() as usize;
+    let (width, height) = (width as usize, height as usize);
+    let blocks_across = width.div_ceil(4);
+    let mut pixels = vec![0; width * height * channels];
+
+    for (i, block) in data.chunks_exact(format.block_size()).enumerate() {
+        let texels = match format {
+            BlockFormat::Bc1 | BlockFormat::Bc1Alpha => decode_bc1(block, true),
+            BlockFormat::Bc3 => {
+                let mut texels = decode_bc1(&block[8..], false);
+                for (texel, alpha) in texels.iter_mut().zip(decode_bc4(&block[..8])) {
+                    texel[3] = alpha;
+                }
+                texels
+            }
+            BlockFormat::Bc5 => {
+                let (red, green) = (decode_bc4(&block[..8]), decode_bc4(&block[8..]));
+                let mut texels = [[0, 0, 0, 255]; 16];
+                for (i, texel) in texels.iter_mut().enumerate() {
+                    texel[0] = red[i];
+                    texel[1] = green[i];
+                }
+                texels
+            }
+            BlockFormat::Bc7 => decode_bc7(block),
+        };
+
+        let (block_x, block_y) = (i % blocks_across * 4, i / blocks_across * 4);
+        for (j, texel) in texels.iter().enumerate() {
+            let (x, y) = (block_x + j % 4, block_y + j / 4);
+            if x < width && y < height {
+                let start = (y * width + x) * channels;
+                pixels[start..start + channels].copy_from_slice(&texel[..channels]);
+            }
+        }
+    }
+
+    Image {
+        width: width as u32,
+        height: height as u32,
+        channels: channels as u32,
+        pixels: Pixels::U8(pixels),
+    }
+}
+
+/// RGBA texels of a BC1 block, row by row. With `allow_alpha`, blocks whose
+/// first color isn't the larger use the 3-color mode with transparent black,
+/// BC3's color blocks always have four colors.
+fn decode_bc1(block: &[u8], allow_alpha: bool) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+
+    let expand = |color: u16| {
+        let (r, g, b) = ((color >> 11) & 31, (color >> 5) & 63, color & 31);
+        [
+            (r << 3 | r >> 2) as u32,
+            (g << 2 | g >> 4) as u32,
+            (b << 3 | b >> 2) as u32,
+        ]
+    };
+    let (e0, e1) = (expand(c0), expand(c1));
+    let mix =
+        |a: u32, b: u32, divisor: u32| [0, 1, 2].map(|i| ((a * e0[i] + b * e1[i]) / divisor) as u8);
+    let rgba = |rgb: [u8; 3], alpha: u8| [rgb[0], rgb[1], rgb[2], alpha];
+
+    let palette = if c0 > c1 || !allow_alpha {
+        [
+            rgba(mix(1, 0, 1), 255),
+            rgba(mix(0, 1, 1), 255),
+            rgba(mix(2, 1, 3), 255),
+            rgba(mix(1, 2, 3), 255),
+        ]
+    } else {
+        [
+            rgba(mix(1, 0, 1), 255),
+            rgba(mix(0, 1, 1), 255),
+            rgba(mix(1, 1, 2), 255),
+            [0, 0, 0, 0],
+        ]
+    };
+
+    let mut texels = [[0; 4]; 16];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = palette[(indices >> (2 * i) & 3) as usize];
+    }
+
+    texels
+}
+
+/// The single channel of a BC4 block (BC3's alpha, each of BC5's two).
+fn decode_bc4(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let mut index_bytes = [0; 8];
+    index_bytes[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(index_bytes);
+
+    let mut palette = [0u8; 8];
+    palette[0] = a0 as u8;
+    palette[1] = a1 as u8;
+    if a0 > a1 {
+        for i in 1..7 {
+            palette[i + 1] = (((7 - i as u32) * a0 + i as u32 * a1) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = (((5 - i as u32) * a0 + i as u32 * a1) / 5) as u8;
+        }
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+
+    let mut values = [0; 16];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = palette[(indices >> (3 * i) & 7) as usize];
+    }
+
+    values
+}
+
+/// Layout of one BC7 mode, see the BC7 format description in the D3D11 or
+/// `ARB_texture_compression_bptc` specs.
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    /// One P bit per endpoint.
+    endpoint_p_bits: bool,
+    /// One P bit per subset, shared by its two endpoints.
+    shared_p_bits: bool,
+    index_bits: u32,
+    /// Separate alpha indices, for modes 4 and 5.
+    secondary_index_bits: u32,
+}
+
+#[rustfmt::skip]
+const BC7_MODES: [Bc7Mode; 8] = [
+    Bc7Mode { subsets: 3, partition_bits: 4, rotation_bits: 0, index_selection_bits: 0, color_bits: 4, alpha_bits: 0, endpoint_p_bits: true, shared_p_bits: false, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 6, alpha_bits: 0, endpoint_p_bits: false, shared_p_bits: true, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 3, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 0, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 0, endpoint_p_bits: true, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 1, color_bits: 5, alpha_bits: 6, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 3 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 0, color_bits: 7, alpha_bits: 8, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 2 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 7, endpoint_p_bits: true, shared_p_bits: false, index_bits: 4, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 5, endpoint_p_bits: true, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+];
+
+/// Two-subset partitions, bit i set where texel i is in subset 1.
+#[rustfmt::skip]
+const BC7_PARTITIONS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80,
+    0xC800, 0xFFEC, 0xFE80, 0xE800, 0xFFE8, 0xFF00, 0xFFF0, 0xF000,
+    0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C,
+    0xAAAA, 0xF0F0, 0x5A5A, 0x33CC, 0x3C3C, 0x55AA, 0x9696, 0xA55A,
+    0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C,
+    0x9336, 0x9CC6, 0x817E, 0xE718, 0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+/// Three-subset partitions, the subset of each texel.
+#[rustfmt::skip]
+const BC7_PARTITIONS_3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+    [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+    [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+    [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+    [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+    [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+    [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+    [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+    [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+    [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+    [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+/// Texels whose index has its top bit left out, per partition: subset 1 of
+/// two, and subsets 1 and 2 of three. Subset 0's is always texel 0.
+#[rustfmt::skip]
+const BC7_ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+    15,  2,  8,  2,  2,  8,  8, 15,  2,  8,  2,  2,  8,  8,  2,  2,
+    15, 15,  6,  8,  2,  8, 15, 15,  2,  8,  2,  2,  2, 15, 15,  6,
+     6,  2,  6,  8, 15, 15,  2,  2, 15, 15, 15, 15, 15,  2,  2, 15,
+];
+
+#[rustfmt::skip]
+const BC7_ANCHORS_3_FIRST: [u8; 64] = [
+     3,  3, 15, 15,  8,  3, 15, 15,  8,  8,  6,  6,  6,  5,  3,  3,
+     3,  3,  8, 15,  3,  3,  6, 10,  5,  8,  8,  6,  8,  5, 15, 15,
+     8, 15,  3,  5,  6, 10,  8, 15, 15,  3, 15,  5, 15, 15, 15, 15,
+     3, 15,  5,  5,  5,  8,  5, 10,  5, 10,  8, 13, 15, 12,  3,  3,
+];
+
+#[rustfmt::skip]
+const BC7_ANCHORS_3_SECOND: [u8; 64] = [
+    15,  8,  8,  3, 15, 15,  3,  8, 15, 15, 15, 15, 15, 15, 15,  8,
+    15,  8, 15,  3, 15,  8, 15,  8,  3, 15,  6, 10, 15, 15, 10,  8,
+    15,  3, 15, 10, 10,  8,  9, 10,  6, 15,  8, 15,  3,  6,  6,  8,
+    15,  3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,  3, 15, 15,  8,
+];
+
+const BC7_WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const BC7_WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const BC7_WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Reads a block's fields from its least significant bit up.
+struct Bits {
+    value: u128,
+}
+
+impl Bits {
+    fn read(&mut self, count: u32) -> u32 {
+        let value = (self.value & ((1 << count) - 1)) as u32;
+        self.value >>= count;
+        value
+    }
+}
+
+fn bc7_subset(subsets: usize, partition: usize, texel: usize) -> usize {
+    match subsets {
+        1 => 0,
+        2 => (BC7_PARTITIONS_2[partition] >> texel & 1) as usize,
+        _ => BC7_PARTITIONS_3[partition][texel] as usize,
+    }
+}
+
+fn bc7_is_anchor(subsets: usize, partition: usize, texel: usize) -> bool {
+    texel == 0
+        || match subsets {
+            1 => false,
+            2 => texel == BC7_ANCHORS_2[partition] as usize,
+            _ => {
+                texel == BC7_ANCHORS_3_FIRST[partition] as usize
+                    || texel == BC7_ANCHORS_3_SECOND[partition] as usize
+            }
+        }
+}
+
+fn bc7_weight(bits: u32, index: u32) -> u32 {
+    match bits {
+        2 => BC7_WEIGHTS_2[index as usize],
+        3 => BC7_WEIGHTS_3[index as usize],
+        _ => BC7_WEIGHTS_4[index as usize],
+    }
+}
+
+fn decode_bc7(block: &[u8]) -> [[u8; 4]; 16] {
+    // a first byte of zero is a reserved mode, which decodes to nothing
+    if block[0] == 0 {
+        return [[0; 4]; 16];
+    }
+
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(block);
+    let mut bits = Bits {
+        value: u128::from_le_bytes(bytes),
+    };
+
+    let mode_index = block[0].trailing_zeros();
+    let mode = &BC7_MODES[mode_index as usize];
+    bits.read(mode_index + 1);
+
+    let partition = bits.read(mode.partition_bits) as usize;
+    let rotation = bits.read(mode.rotation_bits);
+    let index_selection = bits.read(mode.index_selection_bits);
+
+    let endpoint_count = mode.subsets * 2;
+    let mut endpoints = [[0u32; 4]; 6];
+    for channel in 0..3 {
+        for endpoint in &mut endpoints[..endpoint_count] {
+            endpoint[channel] = bits.read(mode.color_bits);
+        }
+    }
+    for endpoint in &mut endpoints[..endpoint_count] {
+        endpoint[3] = bits.read(mode.alpha_bits);
+    }
+
+    let mut p_bits = [None; 6];
+    if mode.endpoint_p_bits {
+        for p_bit in &mut p_bits[..endpoint_count] {
+            *p_bit = Some(bits.read(1));
+        }
+    } else if mode.shared_p_bits {
+        for subset in 0..mode.subsets {
+            let p_bit = Some(bits.read(1));
+            p_bits[subset * 2] = p_bit;
+            p_bits[subset * 2 + 1] = p_bit;
+        }
+    }
+
+    // widen to 8 bits, repeating the top bits in the ones below
+    for (endpoint, p_bit) in endpoints[..endpoint_count].iter_mut().zip(p_bits) {
+        for (channel, value) in endpoint.iter_mut().enumerate() {
+            let mut precision = if channel < 3 {
+                mode.color_bits
+            } else {
+                mode.alpha_bits
+            };
+            if precision == 0 {
+                *value = 255;
+                continue;
+            }
+            if let Some(p_bit) = p_bit {
+                *value = *value << 1 | p_bit;
+                precision += 1;
+            }
+            *value = *value << (8 - precision) | *value >> (2 * precision - 8);
+        }
+    }
+
+    let mut primary = [0; 16];
+    for (texel, index) in primary.iter_mut().enumerate() {
+        let anchor = bc7_is_anchor(mode.subsets, partition, texel);
+        *index = bits.read(mode.index_bits - anchor as u32);
+    }
+    let mut secondary = [0; 16];
+    if mode.secondary_index_bits > 0 {
+        for (texel, index) in secondary.iter_mut().enumerate() {
+            *index = bits.read(mode.secondary_index_bits - (texel == 0) as u32);
+        }
+    }
+
+    let mut texels = [[0; 4]; 16];
+    for (texel, rgba) in texels.iter_mut().enumerate() {
+        let subset = bc7_subset(mode.subsets, partition, texel);
+        let (e0, e1) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+
+        let (color_weight, alpha_weight) = if mode.secondary_index_bits == 0 {
+            let weight = bc7_weight(mode.index_bits, primary[texel]);
+            (weight, weight)
+        } else {
+            let primary = bc7_weight(mode.index_bits, primary[texel]);
+            let secondary = bc7_weight(mode.secondary_index_bits, secondary[texel]);
+            if index_selection == 0 {
+                (primary, secondary)
+            } else {
+                (secondary, primary)
+            }
+        };
+
+        for channel in 0..4 {
+            let weight = if channel < 3 {
+                color_weight
+            } else {
+                alpha_weight
+            };
+            rgba[channel] = (((64 - weight) * e0[channel] + weight * e1[channel] + 32) >> 6) as u8;
+        }
+
+        match rotation {
+            1 => rgba.swap(0, 3),
+            2 => rgba.swap(1, 3),
+            3 => rgba.swap(2, 3),
+            _ => {}
+        }
+    }
+
+    texels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixels(image: &Image) -> &[u8] {
+        match &image.pixels {
+            Pixels::U8(data) => data,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn decodes_bc1_and_bc3() {
+        // pure red and pure blue, then texels using each palette entry
+        let mut block = vec![0x00, 0xF8, 0x1F, 0x00, 0b1110_0100, 0, 0, 0];
+        let image = decompress(BlockFormat::Bc1Alpha, 4, 4, &block);
+
+        let texels: Vec<_> = pixels(&image).chunks(4).take(4).collect();
+        assert_eq!(texels[0], [255, 0, 0, 255]);
+        assert_eq!(texels[1], [0, 0, 255, 255]);
+        assert_eq!(texels[2], [170, 0, 85, 255]);
+        assert_eq!(texels[3], [85, 0, 170, 255]);
+
+        // the same colors swapped switch to three colors and transparency
+        block.swap(0, 2);
+        block.swap(1, 3);
+        let image = decompress(BlockFormat::Bc1Alpha, 4, 4, &block);
+        assert_eq!(&pixels(&image)[8..16], [127, 0, 127, 255, 0, 0, 0, 0]);
+
+        // alpha 255 to 0 in eight steps, the first texel at 255
+        let mut bc3 = vec![255, 0, 0, 0, 0, 0, 0, 0];
+        bc3.extend_from_slice(&[0x00, 0xF8, 0x1F, 0x00, 0, 0, 0, 0]);
+        let image = decompress(BlockFormat::Bc3, 2, 2, &bc3);
+        assert_eq!(image.width, 2);
+        assert_eq!(pixels(&image), [255, 0, 0, 255].repeat(4));
+    }
+
+    #[test]
+    fn decodes_bc5_as_red_and_green() {
+        let mut block = vec![200, 100, 0, 0, 0, 0, 0, 0];
+        block.extend_from_slice(&[10, 20, 0, 0, 0, 0, 0, 0]);
+
+        let image = decompress(BlockFormat::Bc5, 4, 4, &block);
+
+        assert_eq!(image.channels, 3);
+        assert_eq!(&pixels(&image)[..3], [200, 10, 0]);
+    }
+
+    #[test]
+    fn decodes_bc7_mode_6() {
+        // mode 6, all endpoints 127 with P bits set, so everything is 255
+        let mut block = vec![0xC0];
+        block.extend_from_slice(&[0xFF; 15]);
+
+        let image = decompress(BlockFormat::Bc7, 4, 4, &block);
+        assert_eq!(pixels(&image), [255; 64]);
+
+        // endpoints 0 and 254 (P bit 0), indices 0: the first endpoint
+        let mut bits: u128 = 1 << 6;
+        let mut position = 7;
+        for _ in 0..4 {
+            bits |= 127 << (position + 7);
+            position += 14;
+        }
+        let image = decompress(BlockFormat::Bc7, 4, 4, &bits.to_le_bytes());
+        assert_eq!(&pixels(&image)[..4], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn anchors_lie_in_their_subsets() {
+        for partition in 0..64 {
+            assert_eq!(bc7_subset(2, partition, 0), 0);
+            assert_eq!(bc7_subset(3, partition, 0), 0);
+            assert_eq!(
+                bc7_subset(2, partition, BC7_ANCHORS_2[partition] as usize),
+                1
+            );
+            assert_eq!(
+                bc7_subset(3, partition, BC7_ANCHORS_3_FIRST[partition] as usize),
+                1
+            );
+            assert_eq!(
+                bc7_subset(3, partition, BC7_ANCHORS_3_SECOND[partition] as usize),
+                2
+            );
+        }
+    }
+}
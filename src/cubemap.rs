@@ -0,0 +1,256 @@
+//! Building cubemap faces on the CPU: from an equirectangular panorama, or a
+//! procedural starfield when there is no image. `texture::upload_cubemap`
+//! puts them on the GPU.
+//!
+//! Faces are in GL's order, +X, -X, +Y, -Y, +Z, -Z, and laid out as GL reads
+//! them, the first row of each face at its `t = 0` edge.
+
+use crate::texture::{Image, Pixels};
+use std::f32::consts::PI;
+
+/// A direction to look up, not necessarily normalized.
+pub type Direction = glm::Vector3<f32>;
+
+/// The direction through face `face` at face coordinates `s` and `t`, both
+/// from -1 to 1, as in the cube map table of the GL spec.
+pub fn face_direction(face: usize, s: f32, t: f32) -> Direction {
+    match face {
+        0 => glm::vec3(1.0, -t, -s),
+        1 => glm::vec3(-1.0, -t, s),
+        2 => glm::vec3(s, 1.0, t),
+        3 => glm::vec3(s, -1.0, -t),
+        4 => glm::vec3(s, -t, 1.0),
+        _ => glm::vec3(-s, -t, -1.0),
+    }
+}
+
+/// The face `direction` points through, and where on it as `s` and `t` from
+/// -1 to 1. The inverse of `face_direction`.
+pub fn direction_face(direction: Direction) -> (usize, f32, f32) {
+    let (x, y, z) = (direction.x, direction.y, direction.z);
+    let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+
+    if ax >= ay && ax >= az {
+        if x > 0.0 {
+            (0, -z / ax, -y / ax)
+        } else {
+            (1, z / ax, -y / ax)
+        }
+    } else if ay >= az {
+        if y > 0.0 {
+            (2, x / ay, z / ay)
+        } else {
+            (3, x / ay, -z / ay)
+        }
+    } else if z > 0.0 {
+        (4, x / az, -y / az)
+    } else {
+        (5, -x / az, -y / az)
+    }
+}
+
+/// Resamples an equirectangular `image` (longitude across, north pole on the
+/// first row) into six `size` by `size` faces of the same pixel type, 8-bit
+/// for compressed panoramas.
+pub fn from_equirect(image: &Image, size: u32) -> [Image; 6] {
+    if image.pixels.block_format().is_some() {
+        return from_equirect(&image.decompressed(), size);
+    }
+
+    let channels = image.channels as usize;
+
+    [0, 1, 2, 3, 4, 5].map(|face| {
+        let mut values = Vec::with_capacity((size * size) as usize * channels);
+
+        for y in 0..size {
+            for x in 0..size {
+                let s = 2.0 * (x as f32 + 0.5) / size as f32 - 1.0;
+                let t = 2.0 * (y as f32 + 0.5) / size as f32 - 1.0;
+                let direction = glm::normalize(face_direction(face, s, t));
+
+                // longitude 0 looks down -Z, like the front of the camera
+                let u = 0.5 + direction.x.atan2(-direction.z) / (2.0 * PI);
+                let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+                sample_bilinear(
+                    image,
+                    u * image.width as f32,
+                    v * image.height as f32,
+                    &mut values,
+                );
+            }
+        }
+
+        let pixels = if image.pixels.is_float() {
+            Pixels::F32(values)
+        } else {
+            Pixels::U8(
+                values
+                    .iter()
+                    .map(|value| value.round().clamp(0.0, 255.0) as u8)
+                    .collect(),
+            )
+        };
+
+        Image {
+            width: size,
+            height: size,
+            channels: image.channels,
+            pixels,
+        }
+    })
+}
+
+/// Appends every channel of `image` at pixel coordinates `x` and `y`,
+/// wrapping across the sides and clamping at the top and bottom.
+fn sample_bilinear(image: &Image, x: f32, y: f32, values: &mut Vec<f32>) {
+    let (width, height) = (image.width as i64, image.height as i64);
+    let (x, y) = (x - 0.5, y - 0.5);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+
+    let texel = |x: i64, y: i64, channel: usize| {
+        let x = x.rem_euclid(width);
+        let y = y.clamp(0, height - 1);
+        let index = (y * width + x) as usize * image.channels as usize + channel;
+
+        match &image.pixels {
+            Pixels::U8(data) => data[index] as f32,
+            Pixels::F32(data) => data[index],
+            Pixels::Compressed(..) => unreachable!("decompressed by from_equirect"),
+        }
+    };
+
+    let (x0, y0) = (x0 as i64, y0 as i64);
+    for channel in 0..image.channels as usize {
+        let top = texel(x0, y0, channel) * (1.0 - fx) + texel(x0 + 1, y0, channel) * fx;
+        let bottom = texel(x0, y0 + 1, channel) * (1.0 - fx) + texel(x0 + 1, y0 + 1, channel) * fx;
+        values.push(top * (1.0 - fy) + bottom * fy);
+    }
+}
+
+/// Six `size` by `size` RGB faces of black sky with `count` stars scattered
+/// evenly over it. Most are faint and a few bright, and they range from
+/// reddish to bluish white. The same `seed` gives the same sky.
+pub fn starfield(size: u32, count: usize, seed: u64) -> [Image; 6] {
+    let mut faces = [(); 6].map(|_| vec![0u8; (size * size * 3) as usize]);
+    let mut random = Random(seed);
+
+    // from cool red dwarfs to hot blue giants
+    let tints = [
+        [1.0, 0.75, 0.6],
+        [1.0, 0.9, 0.8],
+        [1.0, 1.0, 1.0],
+        [0.8, 0.87, 1.0],
+    ];
+
+    for _ in 0..count {
+        // uniform on the sphere: uniform height and longitude
+        let z = 2.0 * random.next_f32() - 1.0;
+        let longitude = 2.0 * PI * random.next_f32();
+        let radius = (1.0 - z * z).sqrt();
+        let direction = glm::vec3(radius * longitude.cos(), radius * longitude.sin(), z);
+
+        let (face, s, t) = direction_face(direction);
+        let x = (((s + 1.0) / 2.0 * size as f32) as u32).min(size - 1);
+        let y = (((t + 1.0) / 2.0 * size as f32) as u32).min(size - 1);
+
+        let brightness = 255.0 * random.next_f32().powi(6).max(0.08);
+        let tint = tints[(random.next_f32() * tints.len() as f32) as usize % tints.len()];
+
+        let index = ((y * size + x) * 3) as usize;
+        for (channel, factor) in tint.iter().enumerate() {
+            let value = &mut faces[face][index + channel];
+            *value = value.saturating_add((brightness * factor) as u8);
+        }
+    }
+
+    faces.map(|pixels| Image {
+        width: size,
+        height: size,
+        channels: 3,
+        pixels: Pixels::U8(pixels),
+    })
+}
+
+/// SplitMix64, plenty for scattering stars and repeatable on every platform.
+struct Random(u64);
+
+impl Random {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// In `[0, 1)`.
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_face_of_a_direction() {
+        for face in 0..6 {
+            for &(s, t) in &[(0.0, 0.0), (0.5, -0.25), (-0.9, 0.9)] {
+                let (found, found_s, found_t) = direction_face(face_direction(face, s, t));
+
+                assert_eq!(found, face);
+                assert!((found_s - s).abs() < 1e-6 && (found_t - t).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn resamples_a_panorama() {
+        // northern hemisphere red, southern blue
+        let (width, height) = (16, 8);
+        let pixels = (0..width * height)
+            .flat_map(|i| {
+                if i / width < height / 2 {
+                    [255, 0, 0]
+                } else {
+                    [0, 0, 255]
+                }
+            })
+            .collect();
+        let image = Image {
+            width,
+            height,
+            channels: 3,
+            pixels: Pixels::U8(pixels),
+        };
+
+        let faces = from_equirect(&image, 4);
+
+        let center = |face: usize| match &faces[face].pixels {
+            Pixels::U8(data) => data[(2 * 4 + 2) * 3..(2 * 4 + 2) * 3 + 3].to_vec(),
+            _ => unreachable!(),
+        };
+        assert_eq!(center(2), [255, 0, 0]);
+        assert_eq!(center(3), [0, 0, 255]);
+        assert_eq!(faces[0].width, 4);
+        assert_eq!(faces[0].channels, 3);
+    }
+
+    #[test]
+    fn scatters_repeatable_stars() {
+        let sky = starfield(64, 500, 7);
+        let lit = |face: &Image| match &face.pixels {
+            Pixels::U8(data) => data.chunks(3).filter(|rgb| rgb != &[0, 0, 0]).count(),
+            _ => unreachable!(),
+        };
+        let total: usize = sky.iter().map(lit).sum();
+
+        assert_eq!(sky, starfield(64, 500, 7));
+        assert_ne!(sky, starfield(64, 500, 8));
+        // a few stars may share a texel, but none are lost
+        assert!(total > 450 && total <= 500);
+        assert!(sky.iter().all(|face| lit(face) > 0));
+    }
+}
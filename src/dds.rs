@@ -0,0 +1,190 @@
+//! DirectDraw Surface files holding BC1, BC3, BC5 or BC7 textures, flat or
+//! cubemaps, with their mip levels, as written by most texture tools.
+//!
+//! Layout, all numbers little-endian:
+//!
+//! ```text
+//! header   magic "DDS ", 124 byte header: flags @8, height @12, width @16,
+//!          mip count @28 (if flagged), pixel format flags @80 and FourCC
+//!          @84, caps2 @112
+//! DX10     with FourCC "DX10", 20 more bytes: DXGI format @128, misc flags
+//!          @136, array size @140
+//! images   per face in GL's order, per level from the full size one
+//! ```
+
+use crate::compressed::{self, u32_at, BlockFormat, ContainerError};
+use crate::texture::TextureImages;
+use std::fs;
+
+pub const MAGIC: [u8; 4] = *b"DDS ";
+
+const HEADER_SIZE: usize = 128;
+const DX10_HEADER_SIZE: usize = 20;
+
+const FLAGS_MIPMAP_COUNT: u32 = 0x20000;
+const PIXEL_FORMAT_FOURCC: u32 = 0x4;
+const CAPS2_CUBEMAP: u32 = 0x200;
+const MISC_TEXTURE_CUBE: u32 = 0x4;
+
+pub fn load(path: &str) -> Result<TextureImages, ContainerError> {
+    parse(&fs::read(path)?)
+}
+
+pub fn parse(bytes: &[u8]) -> Result<TextureImages, ContainerError> {
+    if bytes.len() < 4 || bytes[..4] != MAGIC {
+        return Err(ContainerError::BadMagic);
+    }
+
+    let height = u32_at(bytes, 12)?;
+    let width = u32_at(bytes, 16)?;
+    let levels = if u32_at(bytes, 8)? & FLAGS_MIPMAP_COUNT != 0 {
+        u32_at(bytes, 28)?.max(1) as usize
+    } else {
+        1
+    };
+    let pixel_format_flags = u32_at(bytes, 80)?;
+    let four_cc = bytes.get(84..88).ok_or(ContainerError::Truncated)?;
+    let mut cubemap = u32_at(bytes, 112)? & CAPS2_CUBEMAP != 0;
+
+    if pixel_format_flags & PIXEL_FORMAT_FOURCC == 0 {
+        return Err(ContainerError::Unsupported(
+            "uncompressed DDS pixel format".to_string(),
+        ));
+    }
+
+    let mut data_start = HEADER_SIZE;
+    let (format, srgb) = match four_cc {
+        b"DXT1" => (BlockFormat::Bc1Alpha, true),
+        b"DXT5" => (BlockFormat::Bc3, true),
+        b"ATI2" | b"BC5U" => (BlockFormat::Bc5, false),
+        b"DX10" => {
+            data_start += DX10_HEADER_SIZE;
+            cubemap = u32_at(bytes, 136)? & MISC_TEXTURE_CUBE != 0;
+            if u32_at(bytes, 140)? > 1 {
+                return Err(ContainerError::Unsupported("texture array".to_string()));
+            }
+
+            match u32_at(bytes, 128)? {
+                71 => (BlockFormat::Bc1Alpha, false),
+                72 => (BlockFormat::Bc1Alpha, true),
+                77 => (BlockFormat::Bc3, false),
+                78 => (BlockFormat::Bc3, true),
+                83 => (BlockFormat::Bc5, false),
+                98 => (BlockFormat::Bc7, false),
+                99 => (BlockFormat::Bc7, true),
+                other => {
+                    return Err(ContainerError::Unsupported(format!(
+                        "DXGI format {}",
+                        other
+                    )))
+                }
+            }
+        }
+        other => {
+            return Err(ContainerError::Unsupported(format!(
+                "FourCC {:?}",
+                String::from_utf8_lossy(other)
+            )))
+        }
+    };
+
+    if width == 0 || height == 0 {
+        return Err(ContainerError::Corrupt("empty image"));
+    }
+    if levels > compressed::max_levels(width, height) {
+        return Err(ContainerError::Corrupt(
+            "more mip levels than the size allows",
+        ));
+    }
+
+    let mut data = bytes.get(data_start..).ok_or(ContainerError::Truncated)?;
+    let faces = (0..if cubemap { 6 } else { 1 })
+        .map(|_| compressed::take_levels(format, width, height, levels, &mut data))
+        .collect::<Result<_, _>>()?;
+
+    Ok(TextureImages { faces, srgb })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::Pixels;
+
+    fn header(width: u32, height: u32, levels: u32, four_cc: &[u8; 4]) -> Vec<u8> {
+        let mut bytes = vec![0; HEADER_SIZE];
+        bytes[..4].copy_from_slice(&MAGIC);
+        bytes[4..8].copy_from_slice(&124u32.to_le_bytes());
+        bytes[12..16].copy_from_slice(&height.to_le_bytes());
+        bytes[16..20].copy_from_slice(&width.to_le_bytes());
+        bytes[8..12].copy_from_slice(&FLAGS_MIPMAP_COUNT.to_le_bytes());
+        bytes[28..32].copy_from_slice(&levels.to_le_bytes());
+        bytes[80..84].copy_from_slice(&PIXEL_FORMAT_FOURCC.to_le_bytes());
+        bytes[84..88].copy_from_slice(four_cc);
+        bytes
+    }
+
+    #[test]
+    fn reads_a_mip_chain() {
+        // 8x4 is two BC1 blocks, then 4x2 and on down are one each
+        let mut bytes = header(8, 4, 4, b"DXT1");
+        bytes.extend((0..5 * 8).map(|i| i as u8));
+
+        let images = parse(&bytes).unwrap();
+
+        assert!(!images.is_cubemap());
+        let levels = &images.faces[0];
+        assert_eq!(
+            levels
+                .iter()
+                .map(|level| (level.width, level.height))
+                .collect::<Vec<_>>(),
+            [(8, 4), (4, 2), (2, 1), (1, 1)]
+        );
+        assert_eq!(
+            levels[1].pixels,
+            Pixels::Compressed(BlockFormat::Bc1Alpha, (16..24).collect())
+        );
+        assert!(matches!(
+            parse(&bytes[..bytes.len() - 1]),
+            Err(ContainerError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn rejects_impossible_mip_counts() {
+        // 1x1 can only have one level, however much data follows
+        let mut bytes = header(1, 1, 40, b"DXT1");
+        bytes.extend_from_slice(&[0; 40 * 8]);
+        assert!(matches!(parse(&bytes), Err(ContainerError::Corrupt(_))));
+
+        // without the flag the count is ignored
+        bytes[8..12].copy_from_slice(&0u32.to_le_bytes());
+        assert_eq!(parse(&bytes).unwrap().faces[0].len(), 1);
+
+        assert_eq!(compressed::level_size(8, 2, 40), (1, 1));
+    }
+
+    #[test]
+    fn reads_dx10_cubemaps() {
+        let mut bytes = header(4, 4, 1, b"DX10");
+        bytes[112..116].copy_from_slice(&CAPS2_CUBEMAP.to_le_bytes());
+        let mut dx10 = [0; DX10_HEADER_SIZE];
+        dx10[..4].copy_from_slice(&99u32.to_le_bytes());
+        dx10[8..12].copy_from_slice(&MISC_TEXTURE_CUBE.to_le_bytes());
+        dx10[12..16].copy_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&dx10);
+        bytes.extend_from_slice(&[0; 6 * 16]);
+
+        let images = parse(&bytes).unwrap();
+
+        assert!(images.is_cubemap() && images.srgb);
+        assert!(matches!(
+            images.faces[5][0].pixels,
+            Pixels::Compressed(BlockFormat::Bc7, _)
+        ));
+        assert!(matches!(
+            parse(&header(4, 4, 1, b"DXT3")),
+            Err(ContainerError::Unsupported(_))
+        ));
+    }
+}
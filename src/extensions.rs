@@ -0,0 +1,34 @@
+//! What the driver offers beyond the GL version the context was made for.
+//! Everything here needs a current context.
+
+use std::ffi::CStr;
+
+/// Whether any of the extensions `names` (e.g. `GL_EXT_texture_sRGB`) is
+/// available.
+pub fn has_any(names: &[&str]) -> bool {
+    let mut count = 0;
+
+    unsafe {
+        gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+
+        (0..count as u32).any(|i| {
+            let name = gl::GetStringi(gl::EXTENSIONS, i);
+            !name.is_null() && {
+                let name = CStr::from_ptr(name as *const _).to_bytes();
+                names.iter().any(|wanted| wanted.as_bytes() == name)
+            }
+        })
+    }
+}
+
+/// The context's GL version, as (major, minor).
+pub fn version() -> (i32, i32) {
+    let (mut major, mut minor) = (0, 0);
+
+    unsafe {
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+    }
+
+    (major, minor)
+}
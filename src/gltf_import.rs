@@ -0,0 +1,622 @@
+//! glTF 2.0 (`.gltf` and `.glb`) importer producing the same `Model` the OBJ
+//! loader does.
+//!
+//! Every mesh instance in the default scene becomes a sub-mesh, with its node
+//! transforms baked into the vertices. Materials refer to textures by path,
+//! so images embedded in the file (GLB buffer views and `data:` URIs) are
+//! written out next to it, as `<model>_image<index>.<png|jpg>`.
+
+use crate::material::Material;
+use crate::mesh::{Indices, Mesh, Vertex};
+use crate::model::{Model, SubMesh};
+use glm::GenSquareMat;
+use gltf::image::Source;
+use gltf::mesh::Mode;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum GltfErrorKind {
+    Gltf(gltf::Error),
+    MissingPositions,
+    UnsupportedMode(Mode),
+    /// An index past the end of its primitive's vertices.
+    IndexOutOfRange(u32),
+    /// A vertex attribute with a different count than the positions.
+    MismatchedAttribute(&'static str),
+    /// An embedded image that can't be read or written out.
+    BadImage(&'static str),
+    ImageIo(io::Error),
+}
+
+#[derive(Debug)]
+pub struct GltfError {
+    pub file: String,
+    pub kind: GltfErrorKind,
+}
+
+impl fmt::Display for GltfErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GltfErrorKind::Gltf(err) => write!(f, "{}", err),
+            GltfErrorKind::MissingPositions => write!(f, "primitive without positions"),
+            GltfErrorKind::UnsupportedMode(mode) => {
+                write!(f, "unsupported primitive mode {:?}", mode)
+            }
+            GltfErrorKind::IndexOutOfRange(index) => {
+                write!(f, "index {} is past the end of the vertices", index)
+            }
+            GltfErrorKind::MismatchedAttribute(name) => {
+                write!(f, "{} count differs from the POSITION count", name)
+            }
+            GltfErrorKind::BadImage(what) => write!(f, "embedded image {}", what),
+            GltfErrorKind::ImageIo(err) => write!(f, "writing embedded image: {}", err),
+        }
+    }
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.file, self.kind)
+    }
+}
+
+impl Error for GltfError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.kind {
+            GltfErrorKind::Gltf(err) => Some(err),
+            GltfErrorKind::ImageIo(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+pub fn load(path: &str) -> Result<Model, GltfError> {
+    let bytes = fs::read(path).map_err(|err| GltfError {
+        file: path.to_string(),
+        kind: GltfErrorKind::Gltf(gltf::Error::Io(err)),
+    })?;
+
+    parse(&bytes, path)
+}
+
+/// Imports glTF JSON or GLB data, `file` labels errors and is where external
+/// buffers and images are resolved from.
+pub fn parse(bytes: &[u8], file: &str) -> Result<Model, GltfError> {
+    let error = |kind| GltfError {
+        file: file.to_string(),
+        kind,
+    };
+    let directory = Path::new(file).parent().unwrap_or_else(|| Path::new(""));
+
+    let gltf::Gltf { document, blob } =
+        gltf::Gltf::from_slice(bytes).map_err(|err| error(GltfErrorKind::Gltf(err)))?;
+    let buffers = gltf::import_buffers(&document, Some(directory), blob)
+        .map_err(|err| error(GltfErrorKind::Gltf(err)))?;
+
+    let images = document
+        .images()
+        .map(|image| image_path(&image, file, &buffers))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(error)?;
+    let materials = document
+        .materials()
+        .map(|material| import_material(&material, &images))
+        .collect();
+
+    let mut model = Model {
+        meshes: vec![],
+        materials,
+    };
+
+    let roots: Vec<_> = match document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    {
+        Some(scene) => scene.nodes().collect(),
+        // without scenes every node tree is shown, from the nodes that are
+        // nobody's child
+        None => {
+            let children: HashSet<_> = document
+                .nodes()
+                .flat_map(|node| node.children().map(|child| child.index()))
+                .collect();
+            document
+                .nodes()
+                .filter(|node| !children.contains(&node.index()))
+                .collect()
+        }
+    };
+
+    for node in roots {
+        import_node(&node, &identity(), &buffers, &mut model).map_err(error)?;
+    }
+
+    Ok(model)
+}
+
+/// `images` has the path of each of the document's images, if it has one.
+fn import_material(material: &gltf::Material, images: &[Option<String>]) -> Material {
+    let name = match (material.name(), material.index()) {
+        (Some(name), _) => name.to_string(),
+        (None, Some(index)) => format!("material{}", index),
+        (None, None) => "default".to_string(),
+    };
+    let pbr = material.pbr_metallic_roughness();
+    let [r, g, b, a] = pbr.base_color_factor();
+    let [er, eg, eb] = material.emissive_factor();
+
+    let mut result = Material::new(&name);
+    result.diffuse = glm::vec3(r, g, b);
+    result.dissolve = a;
+    result.emissive = glm::vec3(er, eg, eb);
+    let path = |texture: gltf::Texture| images[texture.source().index()].clone();
+    result.diffuse_map = pbr
+        .base_color_texture()
+        .and_then(|info| path(info.texture()));
+    result.normal_map = material
+        .normal_texture()
+        .and_then(|normal| path(normal.texture()));
+    result.emissive_map = material
+        .emissive_texture()
+        .and_then(|info| path(info.texture()));
+
+    result
+}
+
+/// Where `image` of the model in `file` can be loaded from: its own file,
+/// or for embedded images a copy written next to the model, rewritten only
+/// when it differs. `None` for embedded formats textures can't load.
+fn image_path(
+    image: &gltf::Image,
+    file: &str,
+    buffers: &[gltf::buffer::Data],
+) -> Result<Option<String>, GltfErrorKind> {
+    let directory = Path::new(file).parent().unwrap_or_else(|| Path::new(""));
+
+    let (bytes, mime_type) = match image.source() {
+        Source::Uri { uri, .. } if !uri.starts_with("data:") => {
+            return Ok(Some(directory.join(uri).to_string_lossy().into_owned()));
+        }
+        Source::Uri { uri, mime_type } => {
+            // data:<mime type>;base64,<data>
+            let (header, data) = uri["data:".len()..]
+                .split_once(";base64,")
+                .ok_or(GltfErrorKind::BadImage("data URI is not base64"))?;
+            let bytes = base64::decode(data)
+                .map_err(|_| GltfErrorKind::BadImage("data URI is not base64"))?;
+            (bytes, mime_type.unwrap_or(header).to_string())
+        }
+        Source::View { view, mime_type } => {
+            let buffer = &buffers[view.buffer().index()].0;
+            let bytes = buffer
+                .get(view.offset()..view.offset() + view.length())
+                .ok_or(GltfErrorKind::BadImage("buffer view is out of range"))?;
+            (bytes.to_vec(), mime_type.to_string())
+        }
+    };
+
+    let extension = match mime_type.as_str() {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        _ => return Ok(None),
+    };
+    let stem = Path::new(file)
+        .file_stem()
+        .map_or("model".into(), |stem| stem.to_string_lossy());
+    let path = directory.join(format!("{}_image{}.{}", stem, image.index(), extension));
+
+    if fs::read(&path).ok().as_deref() != Some(&bytes[..]) {
+        fs::write(&path, &bytes).map_err(GltfErrorKind::ImageIo)?;
+    }
+
+    Ok(Some(path.to_string_lossy().into_owned()))
+}
+
+fn import_node(
+    node: &gltf::Node,
+    parent: &glm::Matrix4<f32>,
+    buffers: &[gltf::buffer::Data],
+    model: &mut Model,
+) -> Result<(), GltfErrorKind> {
+    let [c0, c1, c2, c3] = node.transform().matrix();
+    let column = |c: [f32; 4]| glm::vec4(c[0], c[1], c[2], c[3]);
+    let transform = *parent * glm::Matrix4::new(column(c0), column(c1), column(c2), column(c3));
+
+    if let Some(mesh) = node.mesh() {
+        let name = match (node.name(), mesh.name()) {
+            (Some(name), _) | (None, Some(name)) => name.to_string(),
+            (None, None) => format!("mesh{}", mesh.index()),
+        };
+
+        for primitive in mesh.primitives() {
+            model.meshes.push(SubMesh {
+                name: name.clone(),
+                material: primitive.material().index(),
+                mesh: import_primitive(&primitive, &transform, buffers)?,
+                visible: true,
+            });
+        }
+    }
+
+    for child in node.children() {
+        import_node(&child, &transform, buffers, model)?;
+    }
+
+    Ok(())
+}
+
+fn import_primitive(
+    primitive: &gltf::Primitive,
+    transform: &glm::Matrix4<f32>,
+    buffers: &[gltf::buffer::Data],
+) -> Result<Mesh, GltfErrorKind> {
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+
+    let positions: Vec<[f32; 3]> = reader
+        .read_positions()
+        .ok_or(GltfErrorKind::MissingPositions)?
+        .collect();
+    let count = positions.len();
+    let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(|normals| normals.collect());
+    let has_uvs = reader.read_tex_coords(0).is_some();
+    let uvs: Vec<[f32; 2]> = match reader.read_tex_coords(0) {
+        Some(uvs) => uvs.into_f32().collect(),
+        None => vec![[0.0; 2]; count],
+    };
+    let tangents: Option<Vec<[f32; 4]>> = reader.read_tangents().map(|tangents| tangents.collect());
+
+    for (name, length) in [
+        ("NORMAL", normals.as_ref().map(Vec::len)),
+        ("TEXCOORD_0", Some(uvs.len())),
+        ("TANGENT", tangents.as_ref().map(Vec::len)),
+    ] {
+        if matches!(length, Some(length) if length != count) {
+            return Err(GltfErrorKind::MismatchedAttribute(name));
+        }
+    }
+
+    // normals go through the inverse transpose so non-uniform scales stay
+    // perpendicular to the surface
+    let linear = glm::Matrix3::new(
+        transform.c0.truncate(3),
+        transform.c1.truncate(3),
+        transform.c2.truncate(3),
+    );
+    let normal_matrix = glm::transpose(&linear.inverse().unwrap_or(linear));
+    // mirroring also flips which way the bitangent points
+    let handedness = linear.determinant().signum();
+    let direction = |matrix: &glm::Matrix3<f32>, v: glm::Vector3<f32>| {
+        let v = *matrix * v;
+        if glm::length(v) > 0.0 {
+            glm::normalize(v)
+        } else {
+            v
+        }
+    };
+
+    let vertices = (0..count)
+        .map(|i| {
+            let [x, y, z] = positions[i];
+            let [nx, ny, nz] = normals.as_ref().map_or([0.0; 3], |normals| normals[i]);
+            let [u, v] = uvs[i];
+            let [tx, ty, tz, tw] = tangents.as_ref().map_or([0.0; 4], |tangents| tangents[i]);
+            let tangent = direction(&linear, glm::vec3(tx, ty, tz));
+
+            Vertex {
+                position: (*transform * glm::vec4(x, y, z, 1.0)).truncate(3),
+                // glTF puts v = 0 at the top of the image, OBJ at the bottom
+                uv: glm::vec2(u, 1.0 - v),
+                normal: direction(&normal_matrix, glm::vec3(nx, ny, nz)),
+                tangent: glm::vec4(tangent.x, tangent.y, tangent.z, tw * handedness),
+            }
+        })
+        .collect();
+
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..count as u32).collect(),
+    };
+    if let Some(&index) = indices.iter().find(|&&index| index as usize >= count) {
+        return Err(GltfErrorKind::IndexOutOfRange(index));
+    }
+    let mut triangles = match primitive.mode() {
+        Mode::Triangles => indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect(),
+        Mode::TriangleStrip => (2..indices.len())
+            .map(|i| match i % 2 {
+                0 => [indices[i - 2], indices[i - 1], indices[i]],
+                _ => [indices[i - 1], indices[i - 2], indices[i]],
+            })
+            .collect(),
+        Mode::TriangleFan => (2..indices.len())
+            .map(|i| [indices[0], indices[i - 1], indices[i]])
+            .collect::<Vec<_>>(),
+        mode => return Err(GltfErrorKind::UnsupportedMode(mode)),
+    };
+
+    // a mirroring transform turns counter-clockwise triangles clockwise
+    if handedness < 0.0 {
+        for triangle in &mut triangles {
+            triangle.swap(1, 2);
+        }
+    }
+
+    let indices = triangles.into_iter().flatten().collect();
+
+    let mut mesh = Mesh {
+        vertices,
+        indices: Indices::new(indices, count),
+    };
+
+    // the spec asks for flat normals when a primitive has none
+    if normals.is_none() {
+        mesh.generate_normals(0.0);
+    }
+
+    // and MikkTSpace tangents, which normal maps are baked against
+    if tangents.is_none() && has_uvs {
+        mesh.generate_tangents();
+    }
+
+    Ok(mesh)
+}
+
+fn identity() -> glm::Matrix4<f32> {
+    glm::Matrix4::new(
+        glm::vec4(1.0, 0.0, 0.0, 0.0),
+        glm::vec4(0.0, 1.0, 0.0, 0.0),
+        glm::vec4(0.0, 0.0, 1.0, 0.0),
+        glm::vec4(0.0, 0.0, 0.0, 1.0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One triangle: positions, then normals, then u16 indices (padded).
+    fn triangle_buffer(indices: [u16; 3]) -> Vec<u8> {
+        let mut bytes = vec![];
+        let floats: [f32; 18] = [
+            0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, // positions
+            0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, // normals
+        ];
+        for float in &floats {
+            bytes.extend_from_slice(&float.to_le_bytes());
+        }
+        for index in indices.iter().chain(&[0]) {
+            bytes.extend_from_slice(&index.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn document(buffer: &str, nodes: &str) -> String {
+        format!(
+            r#"{{
+  "asset": {{"version": "2.0"}},
+  "scene": 0,
+  "scenes": [{{"nodes": [0]}}],
+  "nodes": {},
+  "meshes": [{{"name": "Triangle", "primitives": [{{
+    "attributes": {{"POSITION": 0, "NORMAL": 1}}, "indices": 2, "material": 0
+  }}]}}],
+  "materials": [{{
+    "name": "Hull",
+    "pbrMetallicRoughness": {{"baseColorFactor": [1, 0.5, 0.25, 1], "baseColorTexture": {{"index": 0}}}},
+    "normalTexture": {{"index": 0}}
+  }}],
+  "textures": [{{"source": 0}}],
+  "images": [{{"uri": "hull.png"}}],
+  "buffers": [{{"byteLength": 80{}}}],
+  "bufferViews": [
+    {{"buffer": 0, "byteOffset": 0, "byteLength": 72}},
+    {{"buffer": 0, "byteOffset": 72, "byteLength": 6}}
+  ],
+  "accessors": [
+    {{"bufferView": 0, "byteOffset": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+      "min": [0, 0, 0], "max": [1, 1, 0]}},
+    {{"bufferView": 0, "byteOffset": 36, "componentType": 5126, "count": 3, "type": "VEC3"}},
+    {{"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}}
+  ]
+}}"#,
+            nodes, buffer
+        )
+    }
+
+    fn base64(bytes: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut encoded = String::new();
+
+        for chunk in bytes.chunks(3) {
+            let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| {
+                bits | (byte as u32) << (16 - 8 * i)
+            });
+
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 63) as usize] as char);
+                } else {
+                    encoded.push('=');
+                }
+            }
+        }
+
+        encoded
+    }
+
+    fn embedded(nodes: &str) -> String {
+        embedded_buffer(&triangle_buffer([0, 1, 2]), nodes)
+    }
+
+    fn embedded_buffer(buffer: &[u8], nodes: &str) -> String {
+        let uri = format!(
+            r#", "uri": "data:application/octet-stream;base64,{}""#,
+            base64(buffer)
+        );
+        document(&uri, nodes)
+    }
+
+    fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+        let mut json = json.as_bytes().to_vec();
+        while json.len() % 4 != 0 {
+            json.push(b' ');
+        }
+
+        let mut glb = vec![];
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&((12 + 8 + json.len() + 8 + bin.len()) as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(&json);
+        glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(bin);
+        glb
+    }
+
+    #[test]
+    fn imports_embedded_buffers_and_materials() {
+        let model = parse(embedded(r#"[{"mesh": 0}]"#).as_bytes(), "models/ship.gltf").unwrap();
+
+        assert_eq!(model.meshes.len(), 1);
+        let sub_mesh = &model.meshes[0];
+        assert_eq!(sub_mesh.name, "Triangle");
+        assert_eq!(sub_mesh.mesh.vertices.len(), 3);
+        assert_eq!(sub_mesh.mesh.indices.iter().collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(sub_mesh.mesh.vertices[1].position, glm::vec3(1.0, 0.0, 0.0));
+        assert_eq!(sub_mesh.mesh.vertices[1].normal, glm::vec3(0.0, 0.0, 1.0));
+
+        let material = model.material_of(sub_mesh).unwrap();
+        assert_eq!(material.name, "Hull");
+        assert_eq!(material.diffuse, glm::vec3(1.0, 0.5, 0.25));
+        assert_eq!(material.diffuse_map.as_deref(), Some("models/hull.png"));
+        assert_eq!(material.normal_map.as_deref(), Some("models/hull.png"));
+    }
+
+    #[test]
+    fn imports_glb_with_binary_chunk() {
+        let json = document("", r#"[{"mesh": 0}]"#);
+        let model = parse(&glb(&json, &triangle_buffer([0, 1, 2])), "ship.glb").unwrap();
+
+        assert_eq!(model.meshes[0].mesh.triangle_count(), 1);
+    }
+
+    #[test]
+    fn writes_out_embedded_images() {
+        let directory = std::env::temp_dir().join("solar-system-gltf-tests");
+        fs::create_dir_all(&directory).unwrap();
+        let png = b"\x89PNG\r\n\x1a\n not really a png";
+
+        // the image in a buffer view after the triangle
+        let json = document("", r#"[{"mesh": 0}]"#)
+            .replace(
+                r#""uri": "hull.png""#,
+                r#""bufferView": 2, "mimeType": "image/png""#,
+            )
+            .replace(
+                r#""byteLength": 80"#,
+                &format!(r#""byteLength": {}"#, 80 + png.len()),
+            )
+            .replace(
+                r#""byteLength": 6}"#,
+                &format!(
+                    r#""byteLength": 6}}, {{"buffer": 0, "byteOffset": 80, "byteLength": {}}}"#,
+                    png.len()
+                ),
+            );
+        let mut bin = triangle_buffer([0, 1, 2]);
+        bin.extend_from_slice(png);
+        let file = directory.join("ship.glb");
+        let model = parse(&glb(&json, &bin), file.to_str().unwrap()).unwrap();
+
+        let written = directory.join("ship_image0.png");
+        let material = model.material_of(&model.meshes[0]).unwrap();
+        assert_eq!(material.diffuse_map.as_deref(), written.to_str());
+        assert_eq!(material.normal_map.as_deref(), written.to_str());
+        assert_eq!(fs::read(&written).unwrap(), png);
+
+        // and the same from a data URI
+        let uri = format!(r#""uri": "data:image/png;base64,{}""#, base64(png));
+        let json = embedded(r#"[{"mesh": 0}]"#).replace(r#""uri": "hull.png""#, &uri);
+        let file = directory.join("boat.gltf");
+        let model = parse(json.as_bytes(), file.to_str().unwrap()).unwrap();
+
+        let written = directory.join("boat_image0.png");
+        let material = model.material_of(&model.meshes[0]).unwrap();
+        assert_eq!(material.diffuse_map.as_deref(), written.to_str());
+        assert_eq!(fs::read(&written).unwrap(), png);
+    }
+
+    #[test]
+    fn applies_node_transforms() {
+        let nodes = r#"[
+            {"name": "Parent", "translation": [0, 0, 5], "children": [1]},
+            {"name": "Mirrored", "mesh": 0, "scale": [-2, 1, 1]}
+        ]"#;
+        let model = parse(embedded(nodes).as_bytes(), "ship.gltf").unwrap();
+
+        let sub_mesh = &model.meshes[0];
+        assert_eq!(sub_mesh.name, "Mirrored");
+        assert_eq!(
+            sub_mesh.mesh.vertices[1].position,
+            glm::vec3(-2.0, 0.0, 5.0)
+        );
+        assert_eq!(sub_mesh.mesh.vertices[1].normal, glm::vec3(0.0, 0.0, 1.0));
+        // mirrored, so the winding is flipped back to counter-clockwise
+        assert_eq!(sub_mesh.mesh.indices.iter().collect::<Vec<_>>(), [0, 2, 1]);
+    }
+
+    #[test]
+    fn imports_node_trees_without_scenes_once() {
+        let nodes = r#"[
+            {"name": "Parent", "translation": [0, 0, 5], "children": [1]},
+            {"name": "Child", "mesh": 0}
+        ]"#;
+        let json = embedded(nodes)
+            .replace(r#""scene": 0,"#, "")
+            .replace(r#""scenes": [{"nodes": [0]}],"#, "");
+        let model = parse(json.as_bytes(), "ship.gltf").unwrap();
+
+        assert_eq!(model.meshes.len(), 1);
+        assert_eq!(
+            model.meshes[0].mesh.vertices[1].position,
+            glm::vec3(1.0, 0.0, 5.0)
+        );
+    }
+
+    #[test]
+    fn reports_invalid_documents() {
+        let err = parse(b"{", "broken.gltf").unwrap_err();
+
+        assert_eq!(err.file, "broken.gltf");
+        assert!(matches!(err.kind, GltfErrorKind::Gltf(_)));
+    }
+
+    #[test]
+    fn rejects_out_of_range_vertices() {
+        let nodes = r#"[{"mesh": 0}]"#;
+
+        let document = embedded_buffer(&triangle_buffer([0, 1, 7]), nodes);
+        let err = parse(document.as_bytes(), "ship.gltf").unwrap_err();
+        assert!(matches!(err.kind, GltfErrorKind::IndexOutOfRange(7)));
+
+        // two normals for three positions
+        let document = embedded(nodes).replace(
+            r#""byteOffset": 36, "componentType": 5126, "count": 3"#,
+            r#""byteOffset": 36, "componentType": 5126, "count": 2"#,
+        );
+        let err = parse(document.as_bytes(), "ship.gltf").unwrap_err();
+        assert!(matches!(
+            err.kind,
+            GltfErrorKind::MismatchedAttribute("NORMAL")
+        ));
+    }
+}
@@ -0,0 +1,328 @@
+//! Loads any of the supported model formats into a `Model`, picking the
+//! importer by extension or, failing that, by the file's first bytes.
+//!
+//! STL and PLY carry nothing but geometry, so what they lack is generated:
+//! normals with the OBJ loader's crease angle, UVs with a spherical
+//! projection, and tangents from those.
+
+use crate::bake::{self, BakeError};
+use crate::gltf_import::{self, GltfError};
+use crate::mesh::{self, Indices, Mesh, Vertex};
+use crate::model::Model;
+use crate::object::{self, LoadOptions, ObjError};
+use crate::{ply, stl};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Obj,
+    /// The baked format `objbake` writes, see `bake`.
+    Baked,
+    Gltf,
+    Stl,
+    Ply,
+}
+
+impl Format {
+    pub fn from_extension(path: &str) -> Option<Format> {
+        let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+
+        match extension.as_str() {
+            "obj" => Some(Format::Obj),
+            "mesh" => Some(Format::Baked),
+            "gltf" | "glb" => Some(Format::Gltf),
+            "stl" => Some(Format::Stl),
+            "ply" => Some(Format::Ply),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format from the start of the file. OBJ has no magic, so
+    /// text that is nothing else is taken for it.
+    pub fn from_magic(bytes: &[u8]) -> Option<Format> {
+        let text = String::from_utf8_lossy(&bytes[..bytes.len().min(256)]);
+        let start = text.trim_start();
+
+        if bytes.starts_with(&bake::MAGIC) {
+            Some(Format::Baked)
+        } else if bytes.starts_with(b"ply\n") || bytes.starts_with(b"ply\r\n") {
+            Some(Format::Ply)
+        } else if bytes.starts_with(b"glTF") || start.starts_with('{') {
+            Some(Format::Gltf)
+        } else if stl::is_binary(bytes) || start.starts_with("solid") {
+            // binary STL headers may start with "solid" too, the size check
+            // comes first
+            Some(Format::Stl)
+        } else if !bytes.is_empty() && std::str::from_utf8(&bytes[..bytes.len().min(256)]).is_ok() {
+            Some(Format::Obj)
+        } else {
+            None
+        }
+    }
+
+    /// The extension wins when it is a known one.
+    pub fn detect(path: &str, bytes: &[u8]) -> Option<Format> {
+        Format::from_extension(path).or_else(|| Format::from_magic(bytes))
+    }
+}
+
+#[derive(Debug)]
+pub enum ShapeErrorKind {
+    Io(io::Error),
+    BadHeader,
+    BadNumber,
+    IndexOutOfRange,
+    MissingProperty,
+    Truncated,
+    UnsupportedFormat,
+}
+
+/// Error from the STL or PLY readers, laid out like `ObjError`: `line` is 0
+/// for binary data or errors about the whole file.
+#[derive(Debug)]
+pub struct ShapeError {
+    pub file: String,
+    pub line: usize,
+    pub token: String,
+    pub kind: ShapeErrorKind,
+}
+
+impl fmt::Display for ShapeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShapeErrorKind::Io(err) => write!(f, "{}", err),
+            ShapeErrorKind::BadHeader => write!(f, "invalid header"),
+            ShapeErrorKind::BadNumber => write!(f, "invalid number"),
+            ShapeErrorKind::IndexOutOfRange => write!(f, "index out of range"),
+            ShapeErrorKind::MissingProperty => write!(f, "missing property"),
+            ShapeErrorKind::Truncated => write!(f, "unexpected end of file"),
+            ShapeErrorKind::UnsupportedFormat => write!(f, "unsupported format"),
+        }
+    }
+}
+
+impl fmt::Display for ShapeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.file, self.kind)?;
+        } else {
+            write!(f, "{}:{}: {}", self.file, self.line, self.kind)?;
+        }
+
+        if !self.token.is_empty() {
+            write!(f, " '{}'", self.token)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for ShapeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.kind {
+            ShapeErrorKind::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io(String, io::Error),
+    UnknownFormat(String),
+    Obj(ObjError),
+    Bake(String, BakeError),
+    Gltf(GltfError),
+    Shape(ShapeError),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::Io(file, err) => write!(f, "{}: {}", file, err),
+            ImportError::UnknownFormat(file) => write!(f, "{}: unknown model format", file),
+            ImportError::Obj(err) => write!(f, "{}", err),
+            ImportError::Bake(file, err) => write!(f, "{}: {}", file, err),
+            ImportError::Gltf(err) => write!(f, "{}", err),
+            ImportError::Shape(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for ImportError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ImportError::Io(_, err) => Some(err),
+            ImportError::UnknownFormat(_) => None,
+            ImportError::Obj(err) => Some(err),
+            ImportError::Bake(_, err) => Some(err),
+            ImportError::Gltf(err) => Some(err),
+            ImportError::Shape(err) => Some(err),
+        }
+    }
+}
+
+impl From<ObjError> for ImportError {
+    fn from(err: ObjError) -> ImportError {
+        ImportError::Obj(err)
+    }
+}
+
+impl From<GltfError> for ImportError {
+    fn from(err: GltfError) -> ImportError {
+        ImportError::Gltf(err)
+    }
+}
+
+impl From<ShapeError> for ImportError {
+    fn from(err: ShapeError) -> ImportError {
+        ImportError::Shape(err)
+    }
+}
+
+pub fn load(path: &str) -> Result<Model, ImportError> {
+    load_with(path, &LoadOptions::default())
+}
+
+/// OBJ files come from their baked `.mesh` file instead when it is at
+/// least as new, see `bake::load_cached`.
+pub fn load_with(path: &str, options: &LoadOptions) -> Result<Model, ImportError> {
+    if Format::from_extension(path) == Some(Format::Obj) {
+        return bake::load_cached(path, options).map_err(|err| match err {
+            BakeError::Obj(err) => ImportError::Obj(err),
+            err => ImportError::Bake(path.to_string(), err),
+        });
+    }
+
+    let bytes = fs::read(path).map_err(|err| ImportError::Io(path.to_string(), err))?;
+
+    parse_with(&bytes, path, options)
+}
+
+/// Imports `bytes` in whichever format `file` and the bytes point to,
+/// `file` also labels errors and is where referenced files are looked up.
+pub fn parse_with(bytes: &[u8], file: &str, options: &LoadOptions) -> Result<Model, ImportError> {
+    let format =
+        Format::detect(file, bytes).ok_or_else(|| ImportError::UnknownFormat(file.to_string()))?;
+
+    Ok(match format {
+        Format::Obj => object::parse_with(bytes, file, options)?,
+        Format::Baked => {
+            let directory = Path::new(file).parent().unwrap_or_else(|| Path::new(""));
+            bake::parse(bytes, directory).map_err(|err| ImportError::Bake(file.to_string(), err))?
+        }
+        Format::Gltf => gltf_import::parse(bytes, file)?,
+        Format::Stl => stl::parse_with(bytes, file, options)?,
+        Format::Ply => ply::parse_with(bytes, file, options)?,
+    })
+}
+
+/// Mesh of a triangle soup, corners at the same position share a vertex.
+pub(crate) fn weld(positions: &[glm::Vector3<f32>]) -> Mesh {
+    let mut vertices = vec![];
+    let mut unique_vertices = HashMap::new();
+
+    let indices = positions
+        .iter()
+        .map(|&position| {
+            *unique_vertices
+                .entry(mesh::bits(position))
+                .or_insert_with(|| {
+                    vertices.push(Vertex {
+                        position,
+                        uv: glm::vec2(0.0, 0.0),
+                        normal: glm::vec3(0.0, 0.0, 0.0),
+                        tangent: glm::vec4(0.0, 0.0, 0.0, 0.0),
+                    });
+                    (vertices.len() - 1) as u32
+                })
+        })
+        .collect();
+
+    Mesh {
+        indices: Indices::new(indices, vertices.len()),
+        vertices,
+    }
+}
+
+/// Generates whatever the file didn't provide.
+pub(crate) fn complete(mesh: &mut Mesh, has_normals: bool, has_uvs: bool, options: &LoadOptions) {
+    if !has_normals {
+        mesh.generate_normals(options.crease_angle);
+    }
+    if !has_uvs {
+        mesh.generate_spherical_uvs();
+    }
+
+    mesh.generate_tangents();
+}
+
+/// Name for the single part of a file that doesn't name its own.
+pub(crate) fn file_stem(file: &str) -> String {
+    Path::new(file).file_stem().map_or_else(
+        || "default".to_string(),
+        |stem| stem.to_string_lossy().into_owned(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_formats() {
+        assert_eq!(Format::detect("a/Ship.STL", b""), Some(Format::Stl));
+        assert_eq!(Format::detect("earth.glb", b""), Some(Format::Gltf));
+        assert_eq!(Format::detect("moon.obj", b"ply\n"), Some(Format::Obj));
+
+        assert_eq!(
+            Format::detect("eros", b"ply\nformat ascii 1.0\n"),
+            Some(Format::Ply)
+        );
+        assert_eq!(
+            Format::detect("eros", b"glTF\x02\0\0\0"),
+            Some(Format::Gltf)
+        );
+        assert_eq!(
+            Format::detect("eros", b"  {\"asset\": {}}"),
+            Some(Format::Gltf)
+        );
+        assert_eq!(Format::detect("eros", b"solid eros\n"), Some(Format::Stl));
+        assert_eq!(Format::detect("ship.mesh", b""), Some(Format::Baked));
+        assert_eq!(
+            Format::detect("eros", b"SSMB\x03\0\0\0"),
+            Some(Format::Baked)
+        );
+        assert_eq!(Format::detect("eros", b"v 0 0 0\n"), Some(Format::Obj));
+        assert_eq!(Format::detect("eros", b"\xff\xfe\0\x01"), None);
+
+        // a binary STL whose header happens to start like an ASCII one
+        let mut binary = b"solid exported by a CAD tool".to_vec();
+        binary.resize(80, 0);
+        binary.extend(&1u32.to_le_bytes());
+        binary.resize(84 + 50, 0);
+        assert_eq!(Format::from_magic(&binary), Some(Format::Stl));
+        assert!(stl::is_binary(&binary));
+    }
+
+    #[test]
+    fn loads_obj_from_a_fresh_bake() {
+        let directory = std::env::temp_dir().join("solar-system-import-tests");
+        fs::create_dir_all(&directory).unwrap();
+        let source = directory.join("baked.obj");
+        let source = source.to_str().unwrap();
+        fs::write(source, "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+
+        let mut baked = Model::from_mesh("Rock", mesh::icosphere(1), None);
+        baked.meshes.push(baked.meshes[0].clone());
+        bake::save(&baked, &bake::cache_path(source)).unwrap();
+
+        assert_eq!(load(source).unwrap().meshes.len(), 2);
+        assert_eq!(load(&bake::cache_path(source)).unwrap(), baked);
+    }
+}
@@ -0,0 +1,154 @@
+//! KTX 2.0 files holding BC1, BC3, BC5 or BC7 textures, flat or cubemaps,
+//! with their mip levels. Supercompressed (Basis Universal, Zstandard) and
+//! array or 3D textures aren't read.
+//!
+//! Layout, all numbers little-endian:
+//!
+//! ```text
+//! header   12 byte identifier, Vulkan format u32 @12, width @20, height @24,
+//!          depth @28, layers @32, faces @36, levels @40, supercompression @44
+//! index    from @80, per level from the full size one: offset u64,
+//!          length u64, uncompressed length u64
+//! images   anywhere after, each level its faces one after another
+//! ```
+
+use crate::compressed::{self, u32_at, BlockFormat, ContainerError};
+use crate::texture::TextureImages;
+use std::convert::TryFrom;
+use std::fs;
+
+pub const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+const LEVEL_INDEX_START: usize = 80;
+const LEVEL_INDEX_SIZE: usize = 24;
+
+pub fn load(path: &str) -> Result<TextureImages, ContainerError> {
+    parse(&fs::read(path)?)
+}
+
+pub fn parse(bytes: &[u8]) -> Result<TextureImages, ContainerError> {
+    if bytes.len() < IDENTIFIER.len() || bytes[..IDENTIFIER.len()] != IDENTIFIER {
+        return Err(ContainerError::BadMagic);
+    }
+
+    let (format, srgb) = match u32_at(bytes, 12)? {
+        131 => (BlockFormat::Bc1, false),
+        132 => (BlockFormat::Bc1, true),
+        133 => (BlockFormat::Bc1Alpha, false),
+        134 => (BlockFormat::Bc1Alpha, true),
+        137 => (BlockFormat::Bc3, false),
+        138 => (BlockFormat::Bc3, true),
+        141 => (BlockFormat::Bc5, false),
+        145 => (BlockFormat::Bc7, false),
+        146 => (BlockFormat::Bc7, true),
+        other => {
+            return Err(ContainerError::Unsupported(format!(
+                "Vulkan format {}",
+                other
+            )))
+        }
+    };
+
+    let width = u32_at(bytes, 20)?;
+    let height = u32_at(bytes, 24)?;
+    let faces = u32_at(bytes, 36)? as usize;
+    // no levels asks for generated mipmaps
+    let levels = u32_at(bytes, 40)?.max(1) as usize;
+
+    if u32_at(bytes, 28)? > 0 || u32_at(bytes, 32)? > 0 {
+        return Err(ContainerError::Unsupported(
+            "3D or array texture".to_string(),
+        ));
+    }
+    if u32_at(bytes, 44)? != 0 {
+        return Err(ContainerError::Unsupported("supercompression".to_string()));
+    }
+    if faces != 1 && faces != 6 {
+        return Err(ContainerError::Corrupt("face count must be 1 or 6"));
+    }
+    if width == 0 || height == 0 {
+        return Err(ContainerError::Corrupt("empty image"));
+    }
+    if levels > compressed::max_levels(width, height) {
+        return Err(ContainerError::Corrupt(
+            "more mip levels than the size allows",
+        ));
+    }
+
+    let mut images = vec![vec![]; faces];
+    for level in 0..levels {
+        let entry = LEVEL_INDEX_START + level * LEVEL_INDEX_SIZE;
+        let offset = u64_at(bytes, entry)?;
+        let length = u64_at(bytes, entry + 8)?;
+        let mut data = offset
+            .checked_add(length)
+            .and_then(|end| bytes.get(offset..end))
+            .ok_or(ContainerError::Truncated)?;
+
+        let (width, height) = compressed::level_size(width, height, level);
+        for face in &mut images {
+            let mut level_images = compressed::take_levels(format, width, height, 1, &mut data)?;
+            face.append(&mut level_images);
+        }
+    }
+
+    Ok(TextureImages {
+        faces: images,
+        srgb,
+    })
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> Result<usize, ContainerError> {
+    let low = u32_at(bytes, offset)? as u64;
+    let high = u32_at(bytes, offset + 4)? as u64;
+
+    usize::try_from(high << 32 | low).map_err(|_| ContainerError::Truncated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::Pixels;
+
+    #[test]
+    fn reads_levels_from_the_index() {
+        // a 4x4 and a 2x2 BC5 cubemap, the smaller level stored first
+        let mut bytes = vec![0; LEVEL_INDEX_START + 2 * LEVEL_INDEX_SIZE];
+        bytes[..12].copy_from_slice(&IDENTIFIER);
+        for (offset, value) in [(12, 141), (20, 4), (24, 4), (36, 6), (40, 2)] {
+            bytes[offset..offset + 4].copy_from_slice(&(value as u32).to_le_bytes());
+        }
+        let data_start = bytes.len() as u64;
+        let mut index = |level: usize, offset: u64| {
+            let entry = LEVEL_INDEX_START + level * LEVEL_INDEX_SIZE;
+            bytes[entry..entry + 8].copy_from_slice(&offset.to_le_bytes());
+            bytes[entry + 8..entry + 16].copy_from_slice(&96u64.to_le_bytes());
+        };
+        index(1, data_start);
+        index(0, data_start + 96);
+        bytes.extend((0..6).flat_map(|face| [100 + face; 16]));
+        bytes.extend((0..6).flat_map(|face| [face; 16]));
+
+        let images = parse(&bytes).unwrap();
+
+        assert!(images.is_cubemap() && !images.srgb);
+        assert_eq!(images.faces[2][0].width, 4);
+        assert_eq!(
+            images.faces[2][0].pixels,
+            Pixels::Compressed(BlockFormat::Bc5, vec![2; 16])
+        );
+        assert_eq!(images.faces[2][1].width, 2);
+        assert_eq!(
+            images.faces[2][1].pixels,
+            Pixels::Compressed(BlockFormat::Bc5, vec![102; 16])
+        );
+
+        bytes[40..44].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(parse(&bytes), Err(ContainerError::Corrupt(_))));
+
+        bytes[44] = 1;
+        assert!(matches!(parse(&bytes), Err(ContainerError::Unsupported(_))));
+    }
+}
@@ -1,3 +1,24 @@
+pub mod assets;
+pub mod bake;
+pub mod bounds;
+pub mod check;
+pub mod compressed;
+pub mod cubemap;
+pub mod dds;
+pub mod extensions;
+pub mod gltf_import;
+pub mod import;
+pub mod ktx2;
+pub mod lod;
+pub mod material;
+pub mod mesh;
+pub mod model;
 pub mod object;
+pub mod ply;
+pub mod sampler;
 pub mod shaders;
+pub mod skybox;
+pub mod stl;
 pub mod texture;
+pub mod texture_bake;
+pub mod workers;
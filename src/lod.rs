@@ -0,0 +1,553 @@
+//! Level of detail: simplified versions of a mesh, and picking one from how
+//! big the object is on screen.
+//!
+//! Simplification collapses edges in order of their quadric error (Garland &
+//! Heckbert). Collapses move one end onto the other, so vertices keep their
+//! original attributes. Vertices on UV seams, hard edges and open borders
+//! are never moved, which keeps textures and silhouettes intact.
+
+use crate::bounds::BoundingSphere;
+use crate::mesh::{self, Indices, Mesh};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+/// Levels stop once they get smaller than this many triangles.
+const MIN_TRIANGLES: usize = 32;
+
+/// Each level aims for this fraction of the previous one's triangles.
+const LEVEL_RATIO: f32 = 0.5;
+
+/// Builds up to `max_levels` levels, the first being `mesh` itself. Stops
+/// early when simplification no longer makes progress.
+pub fn chain(mesh: &Mesh, max_levels: usize) -> Vec<Mesh> {
+    let mut levels = vec![mesh.clone()];
+
+    while levels.len() < max_levels {
+        let previous = levels.last().unwrap();
+        let target = (previous.triangle_count() as f32 * LEVEL_RATIO) as usize;
+
+        if target < MIN_TRIANGLES {
+            break;
+        }
+
+        let level = simplify(previous, target);
+
+        // locked vertices can leave too little to collapse to be worth it
+        if level.triangle_count() as f32 > previous.triangle_count() as f32 * 0.9 {
+            break;
+        }
+
+        levels.push(level);
+    }
+
+    levels
+}
+
+/// Collapses edges until at most `target_triangles` are left, or nothing more
+/// can be collapsed without flipping faces or tearing the mesh.
+pub fn simplify(mesh: &Mesh, target_triangles: usize) -> Mesh {
+    let mut simplifier = Simplifier::new(mesh);
+    simplifier.run(target_triangles);
+    simplifier.finish()
+}
+
+/// Symmetric 4x4 matrix, upper triangle row by row.
+#[derive(Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    /// Squared distance to the plane `normal . p + d = 0`, scaled by `weight`.
+    fn plane(normal: glm::Vector3<f32>, d: f32, weight: f64) -> Quadric {
+        let (a, b, c, d) = (normal.x as f64, normal.y as f64, normal.z as f64, d as f64);
+
+        Quadric(
+            [
+                a * a,
+                a * b,
+                a * c,
+                a * d,
+                b * b,
+                b * c,
+                b * d,
+                c * c,
+                c * d,
+                d * d,
+            ]
+            .map(|value| value * weight),
+        )
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (value, other) in self.0.iter_mut().zip(&other.0) {
+            *value += other;
+        }
+    }
+
+    fn error(&self, p: glm::Vector3<f32>) -> f64 {
+        let (x, y, z) = (p.x as f64, p.y as f64, p.z as f64);
+        let q = &self.0;
+
+        q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9]
+    }
+}
+
+/// Moving position `from` onto `to`, as queued when both had `versions`.
+struct Collapse {
+    cost: f64,
+    from: usize,
+    to: usize,
+    versions: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Collapse) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Collapse) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    // reversed, so the heap pops the cheapest collapse first
+    fn cmp(&self, other: &Collapse) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+/// Works on welded positions: vertices at the same spot share a position
+/// id, triangles still point at the original vertices.
+struct Simplifier<'a> {
+    mesh: &'a Mesh,
+    position_of: Vec<usize>,
+    positions: Vec<glm::Vector3<f32>>,
+    quadrics: Vec<Quadric>,
+    locked: Vec<bool>,
+    removed: Vec<bool>,
+    versions: Vec<u32>,
+    triangles: Vec<[usize; 3]>,
+    alive: Vec<bool>,
+    alive_count: usize,
+    around: Vec<Vec<usize>>,
+    heap: BinaryHeap<Collapse>,
+}
+
+impl<'a> Simplifier<'a> {
+    fn new(mesh: &'a Mesh) -> Simplifier<'a> {
+        let mut welded = HashMap::new();
+        let mut positions = vec![];
+        let position_of: Vec<usize> = mesh
+            .vertices
+            .iter()
+            .map(|vertex| {
+                *welded
+                    .entry(mesh::weld_key(vertex.position))
+                    .or_insert_with(|| {
+                        positions.push(vertex.position);
+                        positions.len() - 1
+                    })
+            })
+            .collect();
+
+        let triangles: Vec<[usize; 3]> = (0..mesh.triangle_count())
+            .map(|i| {
+                [
+                    mesh.indices.get(i * 3),
+                    mesh.indices.get(i * 3 + 1),
+                    mesh.indices.get(i * 3 + 2),
+                ]
+            })
+            .collect();
+
+        let count = positions.len();
+        let mut quadrics = vec![Quadric::default(); count];
+        let mut around = vec![vec![]; count];
+        let mut vertices_at: Vec<HashSet<usize>> = vec![HashSet::new(); count];
+        let mut edge_uses: HashMap<(usize, usize), usize> = HashMap::new();
+
+        for (i, triangle) in triangles.iter().enumerate() {
+            let [a, b, c] = triangle.map(|vertex| position_of[vertex]);
+            let cross = glm::cross(positions[b] - positions[a], positions[c] - positions[a]);
+            let area = glm::length(cross) * 0.5;
+
+            if area > 0.0 {
+                let normal = glm::normalize(cross);
+                let plane = Quadric::plane(normal, -glm::dot(normal, positions[a]), area as f64);
+
+                for &p in &[a, b, c] {
+                    quadrics[p].add(&plane);
+                }
+            }
+
+            for (&vertex, &p) in triangle.iter().zip(&[a, b, c]) {
+                around[p].push(i);
+                vertices_at[p].insert(vertex);
+            }
+
+            for &(from, to) in &[(a, b), (b, c), (c, a)] {
+                *edge_uses.entry((from.min(to), from.max(to))).or_insert(0) += 1;
+            }
+        }
+
+        // seams and hard edges have several vertices at one position,
+        // borders have edges used by a single triangle
+        let mut locked: Vec<bool> = vertices_at
+            .iter()
+            .map(|vertices| vertices.len() > 1)
+            .collect();
+        for (&(a, b), &uses) in &edge_uses {
+            if uses != 2 {
+                locked[a] = true;
+                locked[b] = true;
+            }
+        }
+
+        let mut simplifier = Simplifier {
+            mesh,
+            position_of,
+            positions,
+            quadrics,
+            locked,
+            removed: vec![false; count],
+            versions: vec![0; count],
+            alive: vec![true; triangles.len()],
+            alive_count: triangles.len(),
+            triangles,
+            around,
+            heap: BinaryHeap::new(),
+        };
+
+        for &(a, b) in edge_uses.keys() {
+            simplifier.push(a, b);
+            simplifier.push(b, a);
+        }
+
+        simplifier
+    }
+
+    fn push(&mut self, from: usize, to: usize) {
+        if self.locked[from] {
+            return;
+        }
+
+        let mut quadric = self.quadrics[from];
+        quadric.add(&self.quadrics[to]);
+
+        self.heap.push(Collapse {
+            cost: quadric.error(self.positions[to]),
+            from,
+            to,
+            versions: (self.versions[from], self.versions[to]),
+        });
+    }
+
+    fn live_triangles(&self, position: usize) -> impl Iterator<Item = usize> + '_ {
+        self.around[position]
+            .iter()
+            .copied()
+            .filter(move |&triangle| self.alive[triangle])
+    }
+
+    fn corners(&self, triangle: usize) -> [usize; 3] {
+        self.triangles[triangle].map(|vertex| self.position_of[vertex])
+    }
+
+    fn neighbors(&self, position: usize) -> HashSet<usize> {
+        self.live_triangles(position)
+            .flat_map(|triangle| self.corners(triangle))
+            .filter(|&p| p != position)
+            .collect()
+    }
+
+    fn run(&mut self, target_triangles: usize) {
+        while self.alive_count > target_triangles {
+            let collapse = match self.heap.pop() {
+                Some(collapse) => collapse,
+                None => break,
+            };
+            let (from, to) = (collapse.from, collapse.to);
+
+            if self.removed[from]
+                || self.removed[to]
+                || collapse.versions != (self.versions[from], self.versions[to])
+            {
+                continue;
+            }
+
+            self.collapse(from, to);
+        }
+    }
+
+    /// Moves `from` onto `to` if that keeps the mesh manifold and no face
+    /// flips over.
+    fn collapse(&mut self, from: usize, to: usize) -> bool {
+        let (shared, others): (Vec<usize>, Vec<usize>) = self
+            .live_triangles(from)
+            .partition(|&triangle| self.corners(triangle).contains(&to));
+
+        if shared.is_empty() {
+            return false;
+        }
+
+        // the two ends may only have the shared triangles' third corners in
+        // common, otherwise the collapse pinches the surface
+        let common = self
+            .neighbors(from)
+            .intersection(&self.neighbors(to))
+            .count();
+        if common != shared.len() {
+            return false;
+        }
+
+        let target = self.positions[to];
+        for &triangle in &others {
+            let [a, b, c] = self.corners(triangle);
+            let before = glm::cross(
+                self.positions[b] - self.positions[a],
+                self.positions[c] - self.positions[a],
+            );
+            let moved = |p: usize| if p == from { target } else { self.positions[p] };
+            let after = glm::cross(moved(b) - moved(a), moved(c) - moved(a));
+
+            // turning a face by more than ~75 degrees, or squashing it to a
+            // sliver, folds the surface
+            let (before_area, after_area) = (glm::length(before), glm::length(after));
+            if after_area <= before_area * 1e-3
+                || glm::dot(before, after) < 0.25 * before_area * after_area
+            {
+                return false;
+            }
+        }
+
+        // `from` isn't on a seam, so one vertex of `to` lines up with it
+        let to_vertex = self.triangles[shared[0]]
+            .iter()
+            .copied()
+            .find(|&vertex| self.position_of[vertex] == to)
+            .unwrap();
+
+        for &triangle in &shared {
+            self.alive[triangle] = false;
+            self.alive_count -= 1;
+        }
+
+        for &triangle in &others {
+            for vertex in self.triangles[triangle].iter_mut() {
+                if self.position_of[*vertex] == from {
+                    *vertex = to_vertex;
+                }
+            }
+        }
+
+        self.removed[from] = true;
+        let quadric = self.quadrics[from];
+        self.quadrics[to].add(&quadric);
+        self.versions[to] += 1;
+        self.around[to].extend(others);
+
+        for neighbor in self.neighbors(to) {
+            self.push(to, neighbor);
+            self.push(neighbor, to);
+        }
+
+        true
+    }
+
+    /// Indexed mesh of the remaining triangles, unused vertices dropped.
+    fn finish(self) -> Mesh {
+        let mut remap = HashMap::new();
+        let mut vertices = vec![];
+        let mut indices = vec![];
+
+        for (triangle, &alive) in self.triangles.iter().zip(&self.alive) {
+            if !alive {
+                continue;
+            }
+
+            for &vertex in triangle {
+                let index = *remap.entry(vertex).or_insert_with(|| {
+                    vertices.push(self.mesh.vertices[vertex]);
+                    (vertices.len() - 1) as u32
+                });
+
+                indices.push(index);
+            }
+        }
+
+        Mesh {
+            indices: Indices::new(indices, vertices.len()),
+            vertices,
+        }
+    }
+}
+
+/// Diameter in pixels of `sphere` (in world space) on a `viewport_height`
+/// pixels tall screen, seen from `camera` with vertical field of view
+/// `fov_y` (radians).
+pub fn screen_size(
+    sphere: &BoundingSphere,
+    camera: glm::Vector3<f32>,
+    fov_y: f32,
+    viewport_height: f32,
+) -> f32 {
+    let distance = glm::length(sphere.center - camera);
+
+    if distance <= sphere.radius {
+        return f32::INFINITY;
+    }
+
+    sphere.radius / (distance * (fov_y * 0.5).tan()) * viewport_height
+}
+
+/// Picks the level to draw for one object, remembering its last choice so
+/// it only switches once the size moved clearly past a threshold.
+#[derive(Clone, Debug)]
+pub struct LodSelector {
+    /// Smallest screen size each level is drawn at, decreasing.
+    thresholds: Vec<f32>,
+    hysteresis: f32,
+    current: usize,
+}
+
+impl LodSelector {
+    /// Level 0 is drawn from `full_detail_size` pixels up. Below that,
+    /// thresholds shrink with the square root of the triangle count, which
+    /// keeps triangles about the same size on screen.
+    pub fn new(triangle_counts: &[usize], full_detail_size: f32) -> LodSelector {
+        let full = triangle_counts.first().copied().unwrap_or(1).max(1) as f32;
+        let mut thresholds: Vec<f32> = triangle_counts
+            .iter()
+            .map(|&count| full_detail_size * (count as f32 / full).sqrt())
+            .collect();
+
+        // the coarsest level takes everything below the others
+        if let Some(last) = thresholds.last_mut() {
+            *last = 0.0;
+        }
+
+        LodSelector {
+            thresholds,
+            hysteresis: 0.15,
+            current: 0,
+        }
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Level for an object `size` pixels across.
+    pub fn select(&mut self, size: f32) -> usize {
+        // coarser once the size drops clearly below this level's threshold
+        while self.current + 1 < self.thresholds.len()
+            && size < self.thresholds[self.current] * (1.0 - self.hysteresis)
+        {
+            self.current += 1;
+        }
+
+        // finer once it grows clearly past the next finer level's threshold
+        while self.current > 0 && size > self.thresholds[self.current - 1] * (1.0 + self.hysteresis)
+        {
+            self.current -= 1;
+        }
+
+        self.current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh;
+
+    fn assert_outward(mesh: &Mesh) {
+        for triangle in 0..mesh.triangle_count() {
+            let p = |i| mesh.vertices[mesh.indices.get(triangle * 3 + i)].position;
+            let normal = glm::cross(p(1) - p(0), p(2) - p(0));
+
+            assert!(glm::dot(normal, p(0) + p(1) + p(2)) > 0.0);
+        }
+    }
+
+    #[test]
+    fn simplifies_a_sphere() {
+        let sphere = mesh::sphere(32, 64);
+        let simplified = simplify(&sphere, 1000);
+
+        assert!(simplified.triangle_count() <= 1000);
+        assert!(simplified.triangle_count() > 500);
+        assert!(simplified.vertices.len() < sphere.vertices.len());
+        assert_outward(&simplified);
+
+        // seam and poles are locked, so no triangle wraps around the texture
+        for triangle in 0..simplified.triangle_count() {
+            let u = |i| {
+                simplified.vertices[simplified.indices.get(triangle * 3 + i)]
+                    .uv
+                    .x
+            };
+            assert!((u(0) - u(1)).abs() < 0.5 && (u(0) - u(2)).abs() < 0.5);
+        }
+    }
+
+    #[test]
+    fn builds_a_shrinking_chain() {
+        let levels = chain(&mesh::icosphere(4), 5);
+
+        assert_eq!(levels.len(), 5);
+        assert_eq!(levels[0], mesh::icosphere(4));
+        for pair in levels.windows(2) {
+            assert!(pair[1].triangle_count() < pair[0].triangle_count());
+            assert_outward(&pair[1]);
+        }
+    }
+
+    #[test]
+    fn screen_size_shrinks_with_distance() {
+        let sphere = BoundingSphere {
+            center: glm::vec3(0.0, 0.0, -10.0),
+            radius: 1.0,
+        };
+        let fov = 90f32.to_radians();
+
+        let near = screen_size(&sphere, glm::vec3(0.0, 0.0, 0.0), fov, 1000.0);
+        let far = screen_size(&sphere, glm::vec3(0.0, 0.0, 10.0), fov, 1000.0);
+
+        assert!((near - 100.0).abs() < 1e-3);
+        assert!((far - 50.0).abs() < 1e-3);
+        assert_eq!(
+            screen_size(&sphere, glm::vec3(0.0, 0.0, -10.5), fov, 1000.0),
+            f32::INFINITY
+        );
+    }
+
+    #[test]
+    fn selection_has_hysteresis() {
+        // thresholds 400, 200, 0
+        let mut selector = LodSelector::new(&[4000, 1000, 250], 400.0);
+
+        assert_eq!(selector.select(500.0), 0);
+        // just under the threshold stays put
+        assert_eq!(selector.select(380.0), 0);
+        assert_eq!(selector.select(300.0), 1);
+        // just over it doesn't come back yet
+        assert_eq!(selector.select(420.0), 1);
+        assert_eq!(selector.select(470.0), 0);
+        assert_eq!(selector.select(10.0), 2);
+        assert_eq!(selector.select(1000.0), 0);
+    }
+}
@@ -1,11 +1,25 @@
 use lazy_static::lazy_static;
-use gl::types::{GLchar, GLuint};
+use gl::types::{GLchar, GLenum, GLint, GLuint};
 use glfw::{Action, Context, CursorMode, Key, OpenGlProfileHint, WindowHint};
-use solar_system::object;
-use solar_system::shaders;
-use solar_system::texture;
+use solar_system::assets::{AssetManager, Handle};
+use solar_system::bounds::{BoundingSphere, Culler};
+use solar_system::cubemap;
+use solar_system::lod::{self, LodSelector};
+use solar_system::material::{self, Material};
+use solar_system::mesh::{self, GpuMesh};
+use solar_system::model::Model;
+use solar_system::sampler::SamplerDesc;
+use solar_system::skybox::Skybox;
+use solar_system::texture::{self, Texture};
+use std::path::Path;
 use std::sync::Mutex;
 
+/// Bodies at least this many pixels across get the full detail mesh.
+const FULL_DETAIL_SIZE: f32 = 300.0;
+
+/// Width of each skybox face in texels.
+const SKYBOX_SIZE: u32 = 1024;
+
 lazy_static! {
     static ref DELTA_TIME: Mutex<f32> = Mutex::new(0.0);
     static ref LAST_FRAME: Mutex<f32> = Mutex::new(0.0);
@@ -38,6 +52,7 @@ fn main() {
     glfw.window_hint(WindowHint::ContextVersionMinor(3));
     glfw.window_hint(WindowHint::OpenGlForwardCompat(true));
     glfw.window_hint(WindowHint::OpenGlProfile(OpenGlProfileHint::Core));
+    glfw.window_hint(WindowHint::SRgbCapable(true));
 
     let (mut window, events) = glfw
         .create_window(1920, 1080, "Solar System", glfw::WindowMode::Windowed)
@@ -55,7 +70,10 @@ fn main() {
         gl::ClearColor(0.0, 0.0, 0.0, 0.0);
         gl::Enable(gl::DEPTH_TEST);
         gl::DepthFunc(gl::LESS);
+        // filter across cubemap face edges, so the sky has no seams
+        gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
     }
+    enable_srgb_output();
 
     let mut vertex_array_id: GLuint = 0;
 
@@ -64,24 +82,46 @@ fn main() {
         gl::BindVertexArray(vertex_array_id);
     }
 
-    let program_id = shaders::load(
-        "./resources/shaders/TransformVertexShader.vertexshader",
-        "./resources/shaders/TextureFragmentShader.fragmentshader",
-    );
+    let mut assets = AssetManager::new("./resources");
+    assets.start_workers();
+
+    let shader = assets
+        .load_shader(
+            "shaders/TransformVertexShader.vertexshader",
+            "shaders/TextureFragmentShader.fragmentshader",
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    let program_id = assets.shader(shader).program;
 
     let mvp = "MVP\0";
-    let my_texture_sampler = "myTextureSampler\0";
+    let m = "M\0";
+    let diffuse_map = "diffuseMap\0";
+    let emissive_map = "emissiveMap\0";
+    let night_map = "nightMap\0";
+    let diffuse_color = "diffuseColor\0";
+    let emissive_color = "emissiveColor\0";
+    let light_position = "LightPosition_worldspace\0";
 
     let matrix_id = unsafe { gl::GetUniformLocation(program_id, mvp.as_ptr() as *const GLchar) };
-    let texture_id =
-        unsafe { gl::GetUniformLocation(program_id, my_texture_sampler.as_ptr() as *const GLchar) };
-
-    unsafe {
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
-    }
+    let model_matrix_id =
+        unsafe { gl::GetUniformLocation(program_id, m.as_ptr() as *const GLchar) };
+    let diffuse_map_id =
+        unsafe { gl::GetUniformLocation(program_id, diffuse_map.as_ptr() as *const GLchar) };
+    let emissive_map_id =
+        unsafe { gl::GetUniformLocation(program_id, emissive_map.as_ptr() as *const GLchar) };
+    let night_map_id =
+        unsafe { gl::GetUniformLocation(program_id, night_map.as_ptr() as *const GLchar) };
+    let diffuse_color_id =
+        unsafe { gl::GetUniformLocation(program_id, diffuse_color.as_ptr() as *const GLchar) };
+    let emissive_color_id =
+        unsafe { gl::GetUniformLocation(program_id, emissive_color.as_ptr() as *const GLchar) };
+
+    let material_uniforms = MaterialUniforms {
+        diffuse_color: diffuse_color_id,
+        emissive_color: emissive_color_id,
+    };
+    let light_position_id =
+        unsafe { gl::GetUniformLocation(program_id, light_position.as_ptr() as *const GLchar) };
 
     window.set_cursor_mode(CursorMode::Disabled);
     window.set_cursor_pos_polling(true);
@@ -99,115 +139,57 @@ fn main() {
         )
     };
 
+    let materials = material::load(&assets.resolve("materials/bodies.mtl"))
+        .unwrap_or_else(|err| panic!("{}", err));
+    let find_material = |name: &str| {
+        materials
+            .iter()
+            .find(|material| material.name == name)
+            .cloned()
+    };
+
+    // every body is the same sphere, only scaled and textured differently
+    let sphere = assets.model_with("sphere(32, 64)", || {
+        Model::from_mesh("Sphere", mesh::sphere(32, 64), None)
+    });
+    let sphere_lods = LodMeshes::new(assets.model(sphere));
+    let body = |name: &str| {
+        let mesh = assets.model(sphere).meshes[0].mesh.clone();
+        Model::from_mesh(name, mesh, find_material(name))
+    };
+
     // 3D OBJECT: SUN
     let mut sun_model = glm::mat4(
         1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
     );
     let mut _sun_mvp = projection * view * sun_model;
 
-    let (sun_vertexes, sun_uvs, _sun_normals) = object::load("./resources/objects/sun.obj");
-
-    let mut sun_vertex_buffer: GLuint = 0;
-
-    unsafe {
-        gl::GenBuffers(1, &mut sun_vertex_buffer);
-        gl::BindBuffer(gl::ARRAY_BUFFER, sun_vertex_buffer);
-        gl::BufferData(
-            gl::ARRAY_BUFFER,
-            (sun_vertexes.len() * std::mem::size_of::<glm::Vector3<f32>>()) as isize,
-            sun_vertexes.as_ptr() as *const std::ffi::c_void,
-            gl::STATIC_DRAW,
-        );
-    }
-
-    let mut sun_uv_buffer: GLuint = 0;
-
-    unsafe {
-        gl::GenBuffers(1, &mut sun_uv_buffer);
-        gl::BindBuffer(gl::ARRAY_BUFFER, sun_uv_buffer);
-        gl::BufferData(
-            gl::ARRAY_BUFFER,
-            (sun_uvs.len() * std::mem::size_of::<glm::Vector2<f32>>()) as isize,
-            sun_uvs.as_ptr() as *const std::ffi::c_void,
-            gl::STATIC_DRAW,
-        );
-    }
+    let sun_object = body("Sun");
+    let mut sun_lod = sphere_lods.selector();
 
     // 3D OBJECT: EARTH
     let mut earth_model;
     let mut earth_mvp;
 
-    let (earth_vertexes, earth_uvs, _earth_normals) =
-        object::load("./resources/objects/earth_apocalypse.obj");
-
-    let mut earth_vertex_buffer: GLuint = 0;
-
-    unsafe {
-        gl::GenBuffers(1, &mut earth_vertex_buffer);
-        gl::BindBuffer(gl::ARRAY_BUFFER, earth_vertex_buffer);
-        gl::BufferData(
-            gl::ARRAY_BUFFER,
-            (earth_vertexes.len() * std::mem::size_of::<glm::Vector3<f32>>()) as isize,
-            earth_vertexes.as_ptr() as *const std::ffi::c_void,
-            gl::STATIC_DRAW,
-        );
-    }
-
-    let mut earth_uv_buffer: GLuint = 0;
-
-    unsafe {
-        gl::GenBuffers(1, &mut earth_uv_buffer);
-        gl::BindBuffer(gl::ARRAY_BUFFER, earth_uv_buffer);
-        gl::BufferData(
-            gl::ARRAY_BUFFER,
-            (earth_uvs.len() * std::mem::size_of::<glm::Vector2<f32>>()) as isize,
-            earth_uvs.as_ptr() as *const std::ffi::c_void,
-            gl::STATIC_DRAW,
-        );
-    }
+    let earth_object = body("Earth");
+    let mut earth_lod = sphere_lods.selector();
 
     // 3D OBJECT: MOON
     let mut moon_model;
     let mut moon_mvp;
 
-    let (moon_vertexes, moon_uvs, _moon_normals) = object::load("./resources/objects/moon.obj");
+    let moon_object = body("Moon");
+    let mut moon_lod = sphere_lods.selector();
 
-    let mut moon_vertex_buffer: GLuint = 0;
-
-    unsafe {
-        gl::GenBuffers(1, &mut moon_vertex_buffer);
-        gl::BindBuffer(gl::ARRAY_BUFFER, moon_vertex_buffer);
-        gl::BufferData(
-            gl::ARRAY_BUFFER,
-            (moon_vertexes.len() * std::mem::size_of::<glm::Vector3<f32>>()) as isize,
-            moon_vertexes.as_ptr() as *const std::ffi::c_void,
-            gl::STATIC_DRAW,
-        );
-    }
-
-    let mut moon_uv_buffer: GLuint = 0;
-
-    unsafe {
-        gl::GenBuffers(1, &mut moon_uv_buffer);
-        gl::BindBuffer(gl::ARRAY_BUFFER, moon_uv_buffer);
-        gl::BufferData(
-            gl::ARRAY_BUFFER,
-            (moon_uvs.len() * std::mem::size_of::<glm::Vector2<f32>>()) as isize,
-            moon_uvs.as_ptr() as *const std::ffi::c_void,
-            gl::STATIC_DRAW,
-        );
-    }
+    // SKYBOX
+    let skybox = load_skybox(&mut assets);
 
     // TEXTURE LOADER
-    let mut textures: [GLuint; 3] = unsafe { std::mem::MaybeUninit::uninit().assume_init() };
-
-    unsafe {
-        gl::GenTextures(3, textures.as_mut_ptr());
-    }
+    let untextured = MaterialTextures::untextured(&mut assets);
 
-    texture::load(textures[0], "./resources/textures/2k_sun.jpg");
-    texture::load(textures[1], "./resources/textures/earth_apocalypse.jpg");
-    texture::load(textures[2], "./resources/textures/2k_moon.jpg");
+    let sun_textures = load_material_textures(&mut assets, &sun_object.materials);
+    let earth_textures = load_material_textures(&mut assets, &earth_object.materials);
+    let moon_textures = load_material_textures(&mut assets, &moon_object.materials);
 
     let mut counter = 0.0;
     let _rotate_speed = 0.5;
@@ -215,7 +197,20 @@ fn main() {
     let mut earth_rotation = 0.0;
     let mut moon_rotation = 0.0;
 
+    let mut title = String::new();
+    let mut loading = true;
+
     while window.get_key(Key::Escape) != Action::Press && !window.should_close() {
+        // placeholders stay up until their textures arrive
+        for err in assets.update() {
+            eprintln!("{}", err);
+        }
+        let progress = assets.progress();
+        if loading && progress.is_done() {
+            loading = false;
+            print_asset_usage(&assets);
+        }
+
         counter += 0.01;
         earth_rotation += 0.3;
         moon_rotation += 1.0;
@@ -225,34 +220,43 @@ fn main() {
             gl::UseProgram(program_id);
         }
 
+        let mut culler = Culler::new(&(projection * view));
+        let camera_position = *CAMERA_POSITION.lock().unwrap();
+        let viewport_height = window.get_framebuffer_size().1 as f32;
+
         /*
         ====================== FIRST OBJECT ======================
         */
         sun_model = glm::ext::rotate(&sun_model, 0.001, glm::vec3(0.0, 1.0, 0.0));
         _sun_mvp = projection * view * sun_model;
 
-        unsafe {
-            gl::Uniform1i(texture_id, 0);
-            gl::UniformMatrix4fv(matrix_id, 1, gl::FALSE, &_sun_mvp[0][0]);
-            gl::ActiveTexture(gl::TEXTURE0);
-            gl::BindTexture(gl::TEXTURE_2D, textures[0]);
-        }
-
-        unsafe {
-            gl::EnableVertexAttribArray(0);
-            gl::BindBuffer(gl::ARRAY_BUFFER, sun_vertex_buffer);
-            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, std::ptr::null());
-        }
+        // the sun is the only light, shining from its center
+        let sun_position = sun_model * glm::vec4(0.0, 0.0, 0.0, 1.0);
 
         unsafe {
-            gl::EnableVertexAttribArray(1);
-            gl::BindBuffer(gl::ARRAY_BUFFER, sun_uv_buffer);
-            gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, 0, std::ptr::null());
+            gl::Uniform3f(
+                light_position_id,
+                sun_position.x,
+                sun_position.y,
+                sun_position.z,
+            );
+            gl::Uniform1i(diffuse_map_id, DIFFUSE_UNIT as GLint);
+            gl::Uniform1i(emissive_map_id, EMISSIVE_UNIT as GLint);
+            gl::Uniform1i(night_map_id, NIGHT_UNIT as GLint);
+            gl::UniformMatrix4fv(matrix_id, 1, gl::FALSE, &_sun_mvp[0][0]);
+            gl::UniformMatrix4fv(model_matrix_id, 1, gl::FALSE, &sun_model[0][0]);
         }
 
-        unsafe {
-            gl::DrawArrays(gl::TRIANGLES, 0, sun_vertexes.len() as i32);
-        }
+        draw_model(
+            &sun_object,
+            sphere_lods.select(&mut sun_lod, &sun_model, camera_position, viewport_height),
+            &sun_textures,
+            &untextured,
+            &assets,
+            &sun_model,
+            &material_uniforms,
+            &mut culler,
+        );
 
         /*
         ====================== SECOND OBJECT ======================
@@ -276,27 +280,25 @@ fn main() {
         earth_mvp = projection * view * earth_model;
 
         unsafe {
-            gl::Uniform1i(texture_id, 0);
             gl::UniformMatrix4fv(matrix_id, 1, gl::FALSE, &earth_mvp[0][0]);
-            gl::ActiveTexture(gl::TEXTURE0);
-            gl::BindTexture(gl::TEXTURE_2D, textures[1]);
-        }
-
-        unsafe {
-            gl::EnableVertexAttribArray(0);
-            gl::BindBuffer(gl::ARRAY_BUFFER, earth_vertex_buffer);
-            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, std::ptr::null());
+            gl::UniformMatrix4fv(model_matrix_id, 1, gl::FALSE, &earth_model[0][0]);
         }
 
-        unsafe {
-            gl::EnableVertexAttribArray(1);
-            gl::BindBuffer(gl::ARRAY_BUFFER, earth_uv_buffer);
-            gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, 0, std::ptr::null());
-        }
-
-        unsafe {
-            gl::DrawArrays(gl::TRIANGLES, 0, earth_vertexes.len() as i32);
-        }
+        draw_model(
+            &earth_object,
+            sphere_lods.select(
+                &mut earth_lod,
+                &earth_model,
+                camera_position,
+                viewport_height,
+            ),
+            &earth_textures,
+            &untextured,
+            &assets,
+            &earth_model,
+            &material_uniforms,
+            &mut culler,
+        );
 
         /*
         ====================== THIRD OBJECT ======================
@@ -319,27 +321,22 @@ fn main() {
         moon_mvp = projection * view * moon_model;
 
         unsafe {
-            gl::Uniform1i(texture_id, 0);
             gl::UniformMatrix4fv(matrix_id, 1, gl::FALSE, &moon_mvp[0][0]);
-            gl::ActiveTexture(gl::TEXTURE0);
-            gl::BindTexture(gl::TEXTURE_2D, textures[2]);
+            gl::UniformMatrix4fv(model_matrix_id, 1, gl::FALSE, &moon_model[0][0]);
         }
 
-        unsafe {
-            gl::EnableVertexAttribArray(0);
-            gl::BindBuffer(gl::ARRAY_BUFFER, moon_vertex_buffer);
-            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, std::ptr::null());
-        }
-
-        unsafe {
-            gl::EnableVertexAttribArray(1);
-            gl::BindBuffer(gl::ARRAY_BUFFER, moon_uv_buffer);
-            gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, 0, std::ptr::null());
-        }
+        draw_model(
+            &moon_object,
+            sphere_lods.select(&mut moon_lod, &moon_model, camera_position, viewport_height),
+            &moon_textures,
+            &untextured,
+            &assets,
+            &moon_model,
+            &material_uniforms,
+            &mut culler,
+        );
 
-        unsafe {
-            gl::DrawArrays(gl::TRIANGLES, 0, moon_vertexes.len() as i32);
-        }
+        skybox.draw(&view, &projection);
 
         process_input(&window);
         view = {
@@ -368,6 +365,22 @@ fn main() {
             gl::DisableVertexAttribArray(2);
         }
 
+        let new_title = if loading {
+            format!(
+                "Solar System (loading {}/{})",
+                progress.finished, progress.total
+            )
+        } else {
+            format!(
+                "Solar System (drawn {}, culled {})",
+                culler.stats.drawn, culler.stats.culled
+            )
+        };
+        if new_title != title {
+            title = new_title;
+            window.set_title(&title);
+        }
+
         window.swap_buffers();
 
         glfw.poll_events();
@@ -376,19 +389,233 @@ fn main() {
         }
     }
 
+    for gpu_mesh in sphere_lods.levels.iter().flatten() {
+        gpu_mesh.delete();
+    }
+
     unsafe {
-        gl::DeleteBuffers(1, &sun_vertex_buffer);
-        gl::DeleteBuffers(1, &sun_uv_buffer);
+        gl::DeleteVertexArrays(1, &vertex_array_id);
+    }
+}
 
-        gl::DeleteBuffers(1, &earth_vertex_buffer);
-        gl::DeleteBuffers(1, &earth_uv_buffer);
+/// A model's levels of detail on the GPU, shared by everything drawn with
+/// it, each with its own `LodSelector`.
+struct LodMeshes {
+    levels: Vec<Vec<GpuMesh>>,
+    triangle_counts: Vec<usize>,
+    sphere: BoundingSphere,
+}
 
-        gl::DeleteBuffers(1, &moon_vertex_buffer);
-        gl::DeleteBuffers(1, &moon_uv_buffer);
+impl LodMeshes {
+    fn new(model: &Model) -> LodMeshes {
+        let levels: Vec<Vec<GpuMesh>> = model.lods(4).iter().map(Model::upload).collect();
+        let triangle_counts: Vec<usize> = levels
+            .iter()
+            .map(|level| {
+                level
+                    .iter()
+                    .map(|gpu_mesh| gpu_mesh.index_count as usize / 3)
+                    .sum()
+            })
+            .collect();
+
+        LodMeshes {
+            triangle_counts,
+            sphere: model.bounds().sphere,
+            levels,
+        }
+    }
 
-        gl::DeleteProgram(program_id);
+    fn selector(&self) -> LodSelector {
+        LodSelector::new(&self.triangle_counts, FULL_DETAIL_SIZE)
+    }
 
-        gl::DeleteVertexArrays(1, &vertex_array_id);
+    /// The level to draw at `model_matrix`, from how big it is on screen.
+    fn select(
+        &self,
+        selector: &mut LodSelector,
+        model_matrix: &glm::Matrix4<f32>,
+        camera_position: glm::Vector3<f32>,
+        viewport_height: f32,
+    ) -> &[GpuMesh] {
+        let size = lod::screen_size(
+            &self.sphere.transform(model_matrix),
+            camera_position,
+            glm::radians(45.0),
+            viewport_height,
+        );
+
+        &self.levels[selector.select(size)]
+    }
+}
+
+/// Has the shaders' output, linear light, encoded as sRGB when written to
+/// the window. Colors look too dark if the framebuffer can't do it.
+fn enable_srgb_output() {
+    let mut encoding = 0;
+
+    unsafe {
+        gl::Enable(gl::FRAMEBUFFER_SRGB);
+        gl::GetFramebufferAttachmentParameteriv(
+            gl::FRAMEBUFFER,
+            gl::BACK_LEFT,
+            gl::FRAMEBUFFER_ATTACHMENT_COLOR_ENCODING,
+            &mut encoding,
+        );
+    }
+
+    if encoding as GLenum != gl::SRGB {
+        eprintln!("the window's framebuffer is not sRGB capable, colors will be too dark");
+    }
+}
+
+/// The sky baked by `texbake --cubemap` into `textures/2k_stars.tex`, or the
+/// panorama in `textures/2k_stars.jpg`, or else a generated starfield.
+fn load_skybox(assets: &mut AssetManager) -> Skybox {
+    let shader = assets
+        .load_shader(
+            "shaders/SkyboxVertexShader.vertexshader",
+            "shaders/SkyboxFragmentShader.fragmentshader",
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    let options = texture::LoadOptions {
+        sampler: SamplerDesc::skybox(),
+        ..texture::LoadOptions::default()
+    };
+
+    let baked = assets.resolve("textures/2k_stars.tex");
+    let panorama = assets.resolve("textures/2k_stars.jpg");
+    let cubemap = if Path::new(&baked).exists() {
+        texture::load_with(&baked, &options)
+            .map_err(|err| eprintln!("{}", err))
+            .ok()
+            .filter(|texture| texture.target == gl::TEXTURE_CUBE_MAP)
+    } else if Path::new(&panorama).exists() {
+        texture::load_equirect_cubemap(&panorama, SKYBOX_SIZE, &options)
+            .map_err(|err| eprintln!("{}", err))
+            .ok()
+    } else {
+        None
+    };
+    let cubemap = cubemap.unwrap_or_else(|| {
+        let faces = cubemap::starfield(SKYBOX_SIZE, 6000, 1);
+        texture::upload_cubemap(&faces, &options).expect("starfield faces are alike")
+    });
+
+    Skybox::new(cubemap, assets.shader(shader).program)
+}
+
+// texture units of the maps, as set on the samplers of the shader
+const DIFFUSE_UNIT: u32 = 0;
+const EMISSIVE_UNIT: u32 = 1;
+const NIGHT_UNIT: u32 = 2;
+
+/// The maps of one material. Missing maps are a single pixel that leaves the
+/// material's colors as they are: white for the diffuse and emissive maps,
+/// which are multiplied by them, and black for the night map, which is added.
+struct MaterialTextures {
+    diffuse: Handle<Texture>,
+    emissive: Handle<Texture>,
+    night: Handle<Texture>,
+}
+
+impl MaterialTextures {
+    fn untextured(assets: &mut AssetManager) -> MaterialTextures {
+        MaterialTextures {
+            diffuse: assets.solid_texture([255, 255, 255]),
+            emissive: assets.solid_texture([255, 255, 255]),
+            night: assets.solid_texture([0, 0, 0]),
+        }
+    }
+
+    fn bind(&self, assets: &AssetManager) {
+        assets.texture(self.diffuse).bind_to_unit(DIFFUSE_UNIT);
+        assets.texture(self.emissive).bind_to_unit(EMISSIVE_UNIT);
+        assets.texture(self.night).bind_to_unit(NIGHT_UNIT);
+    }
+}
+
+/// The maps of each material. They load in the background and are left as if
+/// missing if they fail. Every body is a globe, so the maps are sampled as
+/// planet maps.
+fn load_material_textures(
+    assets: &mut AssetManager,
+    materials: &[Material],
+) -> Vec<MaterialTextures> {
+    let options = texture::LoadOptions {
+        sampler: SamplerDesc::planet(),
+        ..texture::LoadOptions::default()
+    };
+    let mut map = |path: &Option<String>, missing: [u8; 3]| match path {
+        Some(path) => assets.request_texture(path, &options),
+        None => assets.solid_texture(missing),
+    };
+
+    materials
+        .iter()
+        .map(|material| MaterialTextures {
+            diffuse: map(&material.diffuse_map, [255, 255, 255]),
+            emissive: map(&material.emissive_map, [255, 255, 255]),
+            night: map(&material.night_map, [0, 0, 0]),
+        })
+        .collect()
+}
+
+fn print_asset_usage(assets: &AssetManager) {
+    for usage in assets.usage() {
+        println!("{}", usage);
+    }
+    println!(
+        "{:.1} MiB of assets",
+        assets.total_size() as f64 / (1024.0 * 1024.0)
+    );
+}
+
+/// Locations of the per-material uniforms.
+struct MaterialUniforms {
+    diffuse_color: GLint,
+    emissive_color: GLint,
+}
+
+/// Draws the visible parts of `model` with their maps, diffuse and emissive
+/// color, parts without a material get `untextured` in plain white. Parts
+/// outside the view frustum are skipped.
+#[allow(clippy::too_many_arguments)]
+fn draw_model(
+    model: &Model,
+    gpu_meshes: &[GpuMesh],
+    textures: &[MaterialTextures],
+    untextured: &MaterialTextures,
+    assets: &AssetManager,
+    model_matrix: &glm::Matrix4<f32>,
+    uniforms: &MaterialUniforms,
+    culler: &mut Culler,
+) {
+    for (sub_mesh, gpu_mesh) in model.meshes.iter().zip(gpu_meshes) {
+        if !sub_mesh.visible || !culler.is_visible(&gpu_mesh.bounds, model_matrix) {
+            continue;
+        }
+
+        let (texture, color, emissive) = match sub_mesh.material {
+            Some(material) => (
+                &textures[material],
+                model.materials[material].diffuse,
+                model.materials[material].emissive,
+            ),
+            None => (
+                untextured,
+                glm::vec3(1.0, 1.0, 1.0),
+                glm::vec3(0.0, 0.0, 0.0),
+            ),
+        };
+
+        unsafe {
+            gl::Uniform3f(uniforms.diffuse_color, color.x, color.y, color.z);
+            gl::Uniform3f(uniforms.emissive_color, emissive.x, emissive.y, emissive.z);
+        }
+
+        texture.bind(assets);
+        gpu_mesh.draw();
     }
 }
 
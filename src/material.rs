@@ -0,0 +1,324 @@
+use crate::object::{Context, ObjError, ObjErrorKind};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// Surface description from a `newmtl` block of an MTL library. Texture paths
+/// are already resolved against the library's directory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Material {
+    pub name: String,
+    pub ambient: glm::Vector3<f32>,
+    pub diffuse: glm::Vector3<f32>,
+    pub specular: glm::Vector3<f32>,
+    pub emissive: glm::Vector3<f32>,
+    pub shininess: f32,
+    pub dissolve: f32,
+    pub diffuse_map: Option<String>,
+    pub bump_map: Option<String>,
+    pub normal_map: Option<String>,
+    pub specular_map: Option<String>,
+    pub emissive_map: Option<String>,
+    /// Emission shown only on the side facing away from the light, e.g. city
+    /// lights. From `map_Night`, which is our own statement, not part of MTL.
+    pub night_map: Option<String>,
+}
+
+impl Material {
+    pub fn new(name: &str) -> Material {
+        Material {
+            name: name.to_string(),
+            ambient: glm::vec3(0.0, 0.0, 0.0),
+            diffuse: glm::vec3(1.0, 1.0, 1.0),
+            specular: glm::vec3(0.0, 0.0, 0.0),
+            emissive: glm::vec3(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            dissolve: 1.0,
+            diffuse_map: None,
+            bump_map: None,
+            normal_map: None,
+            specular_map: None,
+            emissive_map: None,
+            night_map: None,
+        }
+    }
+}
+
+pub fn load(path: &str) -> Result<Vec<Material>, ObjError> {
+    let file = File::open(path).map_err(|err| ObjError {
+        file: path.to_string(),
+        line: 0,
+        token: String::new(),
+        kind: ObjErrorKind::Io(err),
+    })?;
+
+    parse(BufReader::new(file), path)
+}
+
+/// Parses an MTL library from any reader, texture paths are resolved
+/// relative to the directory of `file`.
+pub fn parse<R: BufRead>(reader: R, file: &str) -> Result<Vec<Material>, ObjError> {
+    let directory = Path::new(file).parent().unwrap_or_else(|| Path::new(""));
+    let mut materials: Vec<Material> = vec![];
+
+    for (line_index, line) in reader.lines().enumerate() {
+        let context = Context {
+            file,
+            line: line_index + 1,
+        };
+
+        let line = line.map_err(|err| context.error("", ObjErrorKind::Io(err)))?;
+        let line = line.split('#').next().unwrap_or_default();
+        let mut tokens = line.split_whitespace();
+
+        let line_type = match tokens.next() {
+            Some(line_type) => line_type,
+            None => continue,
+        };
+
+        if line_type == "newmtl" {
+            let name = tokens.collect::<Vec<_>>().join(" ");
+
+            if name.is_empty() {
+                return Err(context.error(line_type, ObjErrorKind::MissingComponent));
+            }
+
+            materials.push(Material::new(&name));
+            continue;
+        }
+
+        let material = materials
+            .last_mut()
+            .ok_or_else(|| context.error(line_type, ObjErrorKind::MissingComponent))?;
+
+        match line_type {
+            "Ka" => material.ambient = context.color(tokens)?,
+            "Kd" => material.diffuse = context.color(tokens)?,
+            "Ks" => material.specular = context.color(tokens)?,
+            "Ke" => material.emissive = context.color(tokens)?,
+            "Ns" => material.shininess = context.float(tokens.next())?,
+            "d" => material.dissolve = context.float(tokens.next())?,
+            "Tr" => material.dissolve = 1.0 - context.float(tokens.next())?,
+            "map_Kd" => material.diffuse_map = Some(context.map(directory, tokens)?),
+            "map_Bump" | "map_bump" | "bump" => {
+                material.bump_map = Some(context.map(directory, tokens)?)
+            }
+            "norm" => material.normal_map = Some(context.map(directory, tokens)?),
+            "map_Ks" => material.specular_map = Some(context.map(directory, tokens)?),
+            "map_Ke" => material.emissive_map = Some(context.map(directory, tokens)?),
+            "map_Night" => material.night_map = Some(context.map(directory, tokens)?),
+            // recognized, but nothing in the renderer uses them
+            "Ni" | "illum" | "Tf" | "map_Ka" | "map_Ns" | "map_d" | "disp" | "decal" | "refl" => {}
+            _ => return Err(context.error(line_type, ObjErrorKind::UnsupportedDirective)),
+        }
+    }
+
+    Ok(materials)
+}
+
+/// Writes `materials` as an MTL library at `path`, texture paths next to or
+/// below it are made relative again.
+pub fn save(materials: &[Material], path: &str) -> Result<(), ObjError> {
+    let directory = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+
+    File::create(path)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            write(materials, &mut writer, directory)?;
+            writer.flush()
+        })
+        .map_err(|err| ObjError {
+            file: path.to_string(),
+            line: 0,
+            token: String::new(),
+            kind: ObjErrorKind::Io(err),
+        })
+}
+
+/// Writes `materials` as MTL text, texture paths are written relative to
+/// `directory` where possible.
+pub fn write<W: Write>(materials: &[Material], writer: &mut W, directory: &Path) -> io::Result<()> {
+    writeln!(writer, "# solar-system MTL export")?;
+
+    for material in materials {
+        writeln!(writer, "\nnewmtl {}", material.name)?;
+
+        for (statement, color) in &[
+            ("Ka", material.ambient),
+            ("Kd", material.diffuse),
+            ("Ks", material.specular),
+            ("Ke", material.emissive),
+        ] {
+            writeln!(writer, "{} {} {} {}", statement, color.x, color.y, color.z)?;
+        }
+        writeln!(writer, "Ns {}", material.shininess)?;
+        writeln!(writer, "d {}", material.dissolve)?;
+
+        for (statement, map) in &[
+            ("map_Kd", &material.diffuse_map),
+            ("map_Bump", &material.bump_map),
+            ("norm", &material.normal_map),
+            ("map_Ks", &material.specular_map),
+            ("map_Ke", &material.emissive_map),
+            ("map_Night", &material.night_map),
+        ] {
+            if let Some(map) = map {
+                let relative = Path::new(map).strip_prefix(directory);
+                let map = relative.unwrap_or_else(|_| Path::new(map));
+                writeln!(writer, "{} {}", statement, map.to_string_lossy())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+impl<'a> Context<'a> {
+    /// Reads an `r [g b]` color, a lone `r` counts for all three channels.
+    fn color<'b>(
+        &self,
+        mut tokens: impl Iterator<Item = &'b str>,
+    ) -> Result<glm::Vector3<f32>, ObjError> {
+        let r = self.float(tokens.next())?;
+
+        match tokens.next() {
+            Some(g) => Ok(glm::vec3(
+                r,
+                self.float(Some(g))?,
+                self.float(tokens.next())?,
+            )),
+            None => Ok(glm::vec3(r, r, r)),
+        }
+    }
+
+    /// Reads a texture statement, skipping its `-option value...` pairs.
+    fn map<'b>(
+        &self,
+        directory: &Path,
+        tokens: impl Iterator<Item = &'b str>,
+    ) -> Result<String, ObjError> {
+        let mut tokens = tokens.peekable();
+
+        while let Some(option) = tokens
+            .peek()
+            .copied()
+            .filter(|token| token.starts_with('-'))
+        {
+            tokens.next();
+
+            // -o, -s and -t take up to three numbers, everything else takes one
+            let arguments = match option {
+                "-o" | "-s" | "-t" => 3,
+                "-mm" => 2,
+                _ => 1,
+            };
+
+            for i in 0..arguments {
+                match tokens.peek() {
+                    Some(token) if i == 0 || token.parse::<f32>().is_ok() => {
+                        tokens.next();
+                    }
+                    _ => break,
+                }
+            }
+        }
+
+        let name = tokens.collect::<Vec<_>>().join(" ");
+
+        if name.is_empty() {
+            return Err(self.error("", ObjErrorKind::MissingComponent));
+        }
+
+        Ok(directory.join(name).to_string_lossy().into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(source: &str) -> Result<Vec<Material>, ObjError> {
+        parse(source.as_bytes(), "materials/test.mtl")
+    }
+
+    #[test]
+    fn parses_a_blender_library() {
+        let source = "\
+# Blender MTL File: 'solar_system_spheres.blend'
+newmtl Material.001
+Ns 96.078431
+Ka 1.000000 1.000000 1.000000
+Kd 0.640000 0.640000 0.640000
+Ks 0.5
+Ke 0.000000 0.000000 0.000000
+Ni 1.000000
+d 0.5
+illum 2
+map_Kd -s 1 1 1 -bm 0.2 ../textures/2k_sun.jpg
+map_Bump sun bumps.png
+norm sun_normal.png
+
+newmtl Other
+Kd 1 0 0
+";
+        let materials = parse_str(source).unwrap();
+
+        assert_eq!(materials.len(), 2);
+        assert_eq!(materials[0].name, "Material.001");
+        assert_eq!(materials[0].diffuse, glm::vec3(0.64, 0.64, 0.64));
+        assert_eq!(materials[0].specular, glm::vec3(0.5, 0.5, 0.5));
+        assert_eq!(materials[0].dissolve, 0.5);
+        assert_eq!(
+            materials[0].diffuse_map.as_deref(),
+            Some("materials/../textures/2k_sun.jpg")
+        );
+        assert_eq!(
+            materials[0].bump_map.as_deref(),
+            Some("materials/sun bumps.png")
+        );
+        assert_eq!(
+            materials[0].normal_map.as_deref(),
+            Some("materials/sun_normal.png")
+        );
+        assert_eq!(materials[1].diffuse, glm::vec3(1.0, 0.0, 0.0));
+        assert_eq!(materials[1].diffuse_map, None);
+    }
+
+    #[test]
+    fn round_trips_through_write() {
+        let mut moon = Material::new("Moon rock");
+        moon.ambient = glm::vec3(0.1, 0.2, 0.3);
+        moon.specular = glm::vec3(0.5, 0.5, 0.5);
+        moon.emissive = glm::vec3(0.0, 0.25, 0.0);
+        moon.shininess = 96.5;
+        moon.dissolve = 0.75;
+        moon.diffuse_map = Some("materials/textures/moon.jpg".to_string());
+        moon.normal_map = Some("/absolute/moon normal.png".to_string());
+        moon.night_map = Some("materials/textures/moon lights.png".to_string());
+        let materials = vec![moon, Material::new("Plain")];
+
+        let mut text = vec![];
+        write(&materials, &mut text, Path::new("materials")).unwrap();
+        let text = String::from_utf8(text).unwrap();
+
+        assert!(text.contains("map_Kd textures/moon.jpg\n"));
+        assert_eq!(parse_str(&text).unwrap(), materials);
+    }
+
+    #[test]
+    fn reports_statements_before_newmtl() {
+        let err = parse_str("Kd 1 1 1\n").unwrap_err();
+
+        assert_eq!(err.line, 1);
+        assert!(matches!(err.kind, ObjErrorKind::MissingComponent));
+    }
+
+    #[test]
+    fn reports_bad_color() {
+        let err = parse_str("newmtl a\nKd 1 x 1\n").unwrap_err();
+
+        assert_eq!(err.line, 2);
+        assert_eq!(err.token, "x");
+        assert!(matches!(err.kind, ObjErrorKind::BadFloat));
+    }
+}
@@ -0,0 +1,921 @@
+use crate::bounds::Bounds;
+use gl::types::{GLenum, GLuint};
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+/// `tangent.w` is the handedness of the tangent frame, the bitangent is
+/// `cross(normal, tangent.xyz) * tangent.w`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vertex {
+    pub position: glm::Vector3<f32>,
+    pub uv: glm::Vector2<f32>,
+    pub normal: glm::Vector3<f32>,
+    pub tangent: glm::Vector4<f32>,
+}
+
+/// Index buffer, kept as `u16` whenever every vertex fits in it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    /// Picks the smallest index type able to address `vertex_count` vertices.
+    pub fn new(indices: Vec<u32>, vertex_count: usize) -> Indices {
+        if vertex_count <= u16::MAX as usize + 1 {
+            Indices::U16(indices.into_iter().map(|index| index as u16).collect())
+        } else {
+            Indices::U32(indices)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::U16(indices) => indices.len(),
+            Indices::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, i: usize) -> usize {
+        match self {
+            Indices::U16(indices) => indices[i] as usize,
+            Indices::U32(indices) => indices[i] as usize,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len()).map(move |i| self.get(i))
+    }
+
+    pub fn gl_type(&self) -> GLenum {
+        match self {
+            Indices::U16(_) => gl::UNSIGNED_SHORT,
+            Indices::U32(_) => gl::UNSIGNED_INT,
+        }
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        match self {
+            Indices::U16(indices) => indices.len() * std::mem::size_of::<u16>(),
+            Indices::U32(indices) => indices.len() * std::mem::size_of::<u32>(),
+        }
+    }
+
+    fn as_ptr(&self) -> *const std::ffi::c_void {
+        match self {
+            Indices::U16(indices) => indices.as_ptr() as *const std::ffi::c_void,
+            Indices::U32(indices) => indices.as_ptr() as *const std::ffi::c_void,
+        }
+    }
+}
+
+/// Indexed triangle list, every three indices form a face.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Indices,
+}
+
+impl Mesh {
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        Bounds::from_points(self.vertices.iter().map(|vertex| &vertex.position))
+    }
+
+    /// Memory taken by the vertices and indices, on the CPU and again once
+    /// uploaded.
+    pub fn size_in_bytes(&self) -> usize {
+        self.vertices.len() * std::mem::size_of::<Vertex>() + self.indices.size_in_bytes()
+    }
+
+    /// Replaces the normals with generated ones (see `corner_normals`).
+    /// Vertices are welded by position first so UV seams don't turn into hard
+    /// edges, and split again wherever a crease gives one corner a different
+    /// normal.
+    pub fn generate_normals(&mut self, crease_angle: f32) {
+        let mut welded = HashMap::new();
+        let mut positions = vec![];
+        let position_ids: Vec<usize> = self
+            .vertices
+            .iter()
+            .map(|vertex| {
+                *welded.entry(weld_key(vertex.position)).or_insert_with(|| {
+                    positions.push(vertex.position);
+                    positions.len() - 1
+                })
+            })
+            .collect();
+
+        let triangles: Vec<[usize; 3]> = (0..self.triangle_count())
+            .map(|i| {
+                let corner = |j| position_ids[self.indices.get(i * 3 + j)];
+                [corner(0), corner(1), corner(2)]
+            })
+            .collect();
+        let normals = corner_normals(
+            &positions,
+            &triangles,
+            &vec![1; triangles.len()],
+            crease_angle,
+        );
+
+        let corners: Vec<Vertex> = self
+            .indices
+            .iter()
+            .enumerate()
+            .map(|(i, index)| Vertex {
+                normal: normals[i / 3][i % 3],
+                ..self.vertices[index]
+            })
+            .collect();
+
+        self.rebuild(&corners);
+    }
+
+    /// Replaces the UVs with a spherical projection around the bounding
+    /// sphere's center, laid out like `sphere`: `u` goes around the Y axis
+    /// and `v` from the south pole up. Triangles crossing the seam get their
+    /// own copies of the corners past it, so they don't wrap backwards
+    /// through the whole texture.
+    pub fn generate_spherical_uvs(&mut self) {
+        let center = self.bounds().sphere.center;
+        let project = |position: glm::Vector3<f32>| {
+            let direction = position - center;
+            let length = glm::length(direction);
+            if length == 0.0 {
+                return None;
+            }
+
+            let longitude = direction.x.atan2(direction.z);
+            let latitude = (direction.y / length).clamp(-1.0, 1.0).asin();
+            // straight above or below the center `u` is meaningless
+            let on_axis = direction.x.abs() + direction.z.abs() <= length * 1e-6;
+
+            Some(((longitude + PI) / (2.0 * PI), latitude / PI + 0.5, on_axis))
+        };
+
+        let mut corners = Vec::with_capacity(self.indices.len());
+        for triangle in 0..self.triangle_count() {
+            let vertices: Vec<Vertex> = (0..3)
+                .map(|i| self.vertices[self.indices.get(triangle * 3 + i)])
+                .collect();
+            let mut uvs: Vec<_> = vertices
+                .iter()
+                .map(|vertex| project(vertex.position).unwrap_or((0.5, 0.5, true)))
+                .collect();
+
+            let around: Vec<f32> = uvs.iter().filter(|uv| !uv.2).map(|uv| uv.0).collect();
+            let min = around.iter().cloned().fold(f32::INFINITY, f32::min);
+            let max = around.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+            if max - min > 0.5 {
+                for uv in uvs.iter_mut().filter(|uv| uv.0 < 0.5) {
+                    uv.0 += 1.0;
+                }
+            }
+
+            // poles take the `u` of the rest of their triangle
+            let count = around.len().max(1) as f32;
+            let average = uvs.iter().filter(|uv| !uv.2).map(|uv| uv.0).sum::<f32>() / count;
+            for (vertex, uv) in vertices.into_iter().zip(uvs) {
+                let u = if uv.2 { average } else { uv.0 };
+                corners.push(Vertex {
+                    uv: glm::vec2(u, uv.1),
+                    ..vertex
+                });
+            }
+        }
+
+        self.rebuild(&corners);
+    }
+
+    /// Fills in MikkTSpace tangents from the normals and UVs, the same ones
+    /// Blender and most bakers use, so baked normal maps line up. Returns
+    /// false if there is nothing to generate them from.
+    pub fn generate_tangents(&mut self) -> bool {
+        let mut geometry = TangentSpace {
+            mesh: self,
+            tangents: vec![[0.0; 4]; self.indices.len()],
+        };
+
+        if !mikktspace::generate_tangents(&mut geometry) {
+            return false;
+        }
+
+        let corners: Vec<Vertex> = geometry
+            .tangents
+            .iter()
+            .zip(self.indices.iter())
+            .map(|(&[x, y, z, w], index)| Vertex {
+                tangent: glm::vec4(x, y, z, w),
+                ..self.vertices[index]
+            })
+            .collect();
+
+        self.rebuild(&corners);
+
+        true
+    }
+
+    /// Replaces the vertices with one per corner of `corners`, merging the
+    /// corners of each original vertex that still agree with each other.
+    fn rebuild(&mut self, corners: &[Vertex]) {
+        let mut vertices = vec![];
+        let mut unique_vertices = HashMap::new();
+        let mut indices = Vec::with_capacity(corners.len());
+
+        for (corner, index) in corners.iter().zip(self.indices.iter()) {
+            let t = corner.tangent;
+            let key = (
+                index,
+                [corner.uv.x.to_bits(), corner.uv.y.to_bits()],
+                bits(corner.normal),
+                bits(t.truncate(3)),
+                t.w.to_bits(),
+            );
+
+            let new_index = *unique_vertices.entry(key).or_insert_with(|| {
+                vertices.push(*corner);
+                (vertices.len() - 1) as u32
+            });
+
+            indices.push(new_index);
+        }
+
+        self.indices = Indices::new(indices, vertices.len());
+        self.vertices = vertices;
+    }
+}
+
+/// Per-corner view of a mesh for the MikkTSpace generator.
+struct TangentSpace<'a> {
+    mesh: &'a Mesh,
+    tangents: Vec<[f32; 4]>,
+}
+
+impl<'a> TangentSpace<'a> {
+    fn vertex(&self, face: usize, vert: usize) -> &Vertex {
+        &self.mesh.vertices[self.mesh.indices.get(face * 3 + vert)]
+    }
+}
+
+impl<'a> mikktspace::Geometry for TangentSpace<'a> {
+    fn num_faces(&self) -> usize {
+        self.mesh.triangle_count()
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        let p = self.vertex(face, vert).position;
+        [p.x, p.y, p.z]
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        let n = self.vertex(face, vert).normal;
+        [n.x, n.y, n.z]
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        let uv = self.vertex(face, vert).uv;
+        [uv.x, uv.y]
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.tangents[face * 3 + vert] = tangent;
+    }
+}
+
+/// Grid cell `p` falls in for welding. Generated positions are off by
+/// rounding errors, so they are welded on a grid rather than compared
+/// exactly.
+pub(crate) fn weld_key(p: glm::Vector3<f32>) -> [i64; 3] {
+    [p.x, p.y, p.z].map(|x| (x / 1e-5).round() as i64)
+}
+
+/// Exact bit pattern of `v` for use as a map key, with -0.0 counted as 0.0.
+pub(crate) fn bits(v: glm::Vector3<f32>) -> [u32; 3] {
+    [v.x, v.y, v.z].map(|x| (x + 0.0).to_bits())
+}
+
+/// Area-weighted normal of every corner of `triangles`, which index into
+/// `positions`.
+///
+/// A corner averages the faces around its position that are in the same
+/// smoothing group and bend away from its own face by at most
+/// `crease_angle` radians. Faces in group 0 are flat shaded.
+pub fn corner_normals(
+    positions: &[glm::Vector3<f32>],
+    triangles: &[[usize; 3]],
+    smoothing_groups: &[u32],
+    crease_angle: f32,
+) -> Vec<[glm::Vector3<f32>; 3]> {
+    let unit = |v: glm::Vector3<f32>| {
+        if glm::length(v) > 0.0 {
+            glm::normalize(v)
+        } else {
+            v
+        }
+    };
+
+    // the cross product is twice the face's area long, which is the weight
+    let face_normals: Vec<_> = triangles
+        .iter()
+        .map(|&[a, b, c]| glm::cross(positions[b] - positions[a], positions[c] - positions[a]))
+        .collect();
+    let unit_normals: Vec<_> = face_normals.iter().map(|&normal| unit(normal)).collect();
+
+    let mut faces_around: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (face, triangle) in triangles.iter().enumerate() {
+        for &position in triangle {
+            faces_around.entry(position).or_default().push(face);
+        }
+    }
+
+    let min_cos = crease_angle.cos();
+
+    triangles
+        .iter()
+        .enumerate()
+        .map(|(face, triangle)| {
+            let (own, group) = (unit_normals[face], smoothing_groups[face]);
+
+            if group == 0 {
+                return [own; 3];
+            }
+
+            let mut normals = [own; 3];
+            for (normal, position) in normals.iter_mut().zip(triangle) {
+                let sum = faces_around[position]
+                    .iter()
+                    .filter(|&&other| {
+                        other == face
+                            || (smoothing_groups[other] == group
+                                && glm::dot(unit_normals[other], own) >= min_cos)
+                    })
+                    .fold(glm::vec3(0.0, 0.0, 0.0), |sum, &other| {
+                        sum + face_normals[other]
+                    });
+
+                *normal = unit(sum);
+            }
+
+            normals
+        })
+        .collect()
+}
+
+/// Unit UV-sphere around the origin with `stacks` rings from pole to pole and
+/// `slices` segments around the Y axis.
+///
+/// UVs follow the Blender convention: `u` goes once around starting at -Z,
+/// `v` is 1 at the north pole (+Y). The seam column is duplicated so `u`
+/// reaches 1 without wrapping.
+pub fn sphere(stacks: usize, slices: usize) -> Mesh {
+    let (stacks, slices) = (stacks.max(2), slices.max(3));
+    let mut vertices = Vec::with_capacity((stacks + 1) * (slices + 1));
+
+    for i in 0..=stacks {
+        let v = 1.0 - i as f32 / stacks as f32;
+
+        for j in 0..=slices {
+            let mut u = j as f32 / slices as f32;
+
+            // pole vertices sit in the middle of their triangle's edge
+            if i == 0 || i == stacks {
+                u = ((j as f32 + 0.5) / slices as f32).min(1.0);
+            }
+
+            vertices.push(sphere_vertex(u, v));
+        }
+    }
+
+    let mut indices = Vec::with_capacity(6 * stacks * slices);
+    let row = slices as u32 + 1;
+
+    for i in 0..stacks as u32 {
+        for j in 0..slices as u32 {
+            let top_left = i * row + j;
+            let bottom_left = (i + 1) * row + j;
+
+            // the first and last rings are triangle fans around the poles
+            if i != stacks as u32 - 1 {
+                indices.extend_from_slice(&[top_left, bottom_left, bottom_left + 1]);
+            }
+
+            if i != 0 {
+                indices.extend_from_slice(&[top_left, bottom_left + 1, top_left + 1]);
+            }
+        }
+    }
+
+    Mesh {
+        indices: Indices::new(indices, vertices.len()),
+        vertices,
+    }
+}
+
+/// Unit icosphere around the origin, each subdivision splits every triangle
+/// in four. UVs use the same mapping as `sphere`, with vertices duplicated
+/// along the seam and at the poles so no triangle wraps around the texture.
+/// Faces crossing the seam get `u` past 1, so sample with repeat wrapping.
+pub fn icosphere(subdivisions: usize) -> Mesh {
+    // an icosahedron standing on its poles: two rings of five between them
+    let ring_y = (0.5f32).atan().sin();
+    let ring_radius = (0.5f32).atan().cos();
+
+    let mut positions = vec![glm::vec3(0.0, 1.0, 0.0)];
+    for k in 0..10 {
+        let angle = k as f32 * PI / 5.0;
+        let y = if k % 2 == 0 { ring_y } else { -ring_y };
+
+        positions.push(glm::vec3(
+            ring_radius * angle.sin(),
+            y,
+            ring_radius * angle.cos(),
+        ));
+    }
+    positions.push(glm::vec3(0.0, -1.0, 0.0));
+
+    let mut triangles = vec![];
+    for k in 0..5 {
+        let upper = 1 + 2 * k;
+        let lower = upper + 1;
+        let next_upper = 1 + (2 * k + 2) % 10;
+        let next_lower = 1 + (2 * k + 3) % 10;
+
+        triangles.push([0, upper, next_upper]);
+        triangles.push([upper, lower, next_upper]);
+        triangles.push([next_upper, lower, next_lower]);
+        triangles.push([11, next_lower, lower]);
+    }
+
+    for _ in 0..subdivisions {
+        let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut midpoint = |a: usize, b: usize, positions: &mut Vec<glm::Vector3<f32>>| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                positions.push(glm::normalize((positions[a] + positions[b]) * 0.5));
+                positions.len() - 1
+            })
+        };
+
+        triangles = triangles
+            .iter()
+            .flat_map(|&[a, b, c]| {
+                let ab = midpoint(a, b, &mut positions);
+                let bc = midpoint(b, c, &mut positions);
+                let ca = midpoint(c, a, &mut positions);
+
+                vec![[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    let mut unique_vertices: HashMap<(usize, u32), u32> = HashMap::new();
+
+    for mut triangle in triangles {
+        let (a, b, c) = (
+            positions[triangle[0]],
+            positions[triangle[1]],
+            positions[triangle[2]],
+        );
+
+        // keep every face counter-clockwise seen from outside
+        if glm::dot(glm::cross(b - a, c - a), a + b + c) < 0.0 {
+            triangle.swap(1, 2);
+        }
+
+        let mut us: Vec<Option<f32>> = triangle
+            .iter()
+            .map(|&i| {
+                let position = positions[i];
+
+                if position.x.abs() < 1e-6 && position.z.abs() < 1e-6 {
+                    None
+                } else if position.x.abs() < 1e-6 && position.z < 0.0 {
+                    // on the seam itself, moved to 1 below when needed
+                    Some(0.0)
+                } else {
+                    Some((position.x.atan2(position.z) + PI) / (2.0 * PI))
+                }
+            })
+            .collect();
+
+        // a triangle straddling the seam gets its low side shifted past 1
+        let known: Vec<f32> = us.iter().flatten().copied().collect();
+        let lowest = known.iter().copied().fold(f32::INFINITY, f32::min);
+        let highest = known.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        if highest - lowest > 0.5 {
+            for u in us.iter_mut().flatten() {
+                if *u < 0.5 {
+                    *u += 1.0;
+                }
+            }
+        }
+
+        // a pole takes the middle of the opposite edge
+        let known: Vec<f32> = us.iter().flatten().copied().collect();
+        let middle = known.iter().sum::<f32>() / known.len() as f32;
+
+        for (&position_index, u) in triangle.iter().zip(us) {
+            let position = positions[position_index];
+            let u = u.unwrap_or(middle);
+            let v = 0.5 + position.y.clamp(-1.0, 1.0).asin() / PI;
+
+            let index = *unique_vertices
+                .entry((position_index, u.to_bits()))
+                .or_insert_with(|| {
+                    vertices.push(Vertex {
+                        position,
+                        uv: glm::vec2(u, v),
+                        normal: position,
+                        tangent: sphere_tangent(u),
+                    });
+                    (vertices.len() - 1) as u32
+                });
+
+            indices.push(index);
+        }
+    }
+
+    Mesh {
+        indices: Indices::new(indices, vertices.len()),
+        vertices,
+    }
+}
+
+/// Point of the unit sphere at texture coordinates (`u`, `v`).
+fn sphere_vertex(u: f32, v: f32) -> Vertex {
+    let longitude = u * 2.0 * PI - PI;
+    let latitude = (v - 0.5) * PI;
+
+    let normal = glm::vec3(
+        latitude.cos() * longitude.sin(),
+        latitude.sin(),
+        latitude.cos() * longitude.cos(),
+    );
+
+    Vertex {
+        position: normal,
+        uv: glm::vec2(u, v),
+        normal,
+        tangent: sphere_tangent(u),
+    }
+}
+
+/// Direction of growing `u` around the Y axis, the bitangent then points
+/// north like `v` does.
+fn sphere_tangent(u: f32) -> glm::Vector4<f32> {
+    let longitude = u * 2.0 * PI - PI;
+
+    glm::vec4(longitude.cos(), 0.0, -longitude.sin(), 1.0)
+}
+
+/// A mesh uploaded to the GPU, with the vertices interleaved in one buffer.
+pub struct GpuMesh {
+    pub vertex_buffer: GLuint,
+    pub index_buffer: GLuint,
+    pub index_count: i32,
+    pub index_type: GLenum,
+    /// Whether the mesh came with tangents worth binding to attribute 3.
+    pub has_tangents: bool,
+    /// In the mesh's own space, for culling.
+    pub bounds: Bounds,
+}
+
+impl GpuMesh {
+    pub fn upload(mesh: &Mesh) -> GpuMesh {
+        let (mut vertex_buffer, mut index_buffer): (GLuint, GLuint) = (0, 0);
+
+        unsafe {
+            gl::GenBuffers(1, &mut vertex_buffer);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vertex_buffer);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (mesh.vertices.len() * std::mem::size_of::<Vertex>()) as isize,
+                mesh.vertices.as_ptr() as *const std::ffi::c_void,
+                gl::STATIC_DRAW,
+            );
+        }
+
+        unsafe {
+            gl::GenBuffers(1, &mut index_buffer);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, index_buffer);
+            gl::BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                mesh.indices.size_in_bytes() as isize,
+                mesh.indices.as_ptr(),
+                gl::STATIC_DRAW,
+            );
+        }
+
+        GpuMesh {
+            vertex_buffer,
+            index_buffer,
+            index_count: mesh.indices.len() as i32,
+            index_type: mesh.indices.gl_type(),
+            has_tangents: mesh.vertices.iter().any(|vertex| vertex.tangent.w != 0.0),
+            bounds: mesh.bounds(),
+        }
+    }
+
+    /// Binds the buffers to attributes 0 (position), 1 (uv), 2 (normal) and,
+    /// if there are tangents, 3 (tangent) and issues the draw call.
+    pub fn draw(&self) {
+        let stride = std::mem::size_of::<Vertex>() as i32;
+        let uv_offset = std::mem::size_of::<glm::Vector3<f32>>();
+        let normal_offset = uv_offset + std::mem::size_of::<glm::Vector2<f32>>();
+        let tangent_offset = normal_offset + std::mem::size_of::<glm::Vector3<f32>>();
+
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vertex_buffer);
+
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, stride, std::ptr::null());
+
+            gl::EnableVertexAttribArray(1);
+            gl::VertexAttribPointer(
+                1,
+                2,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                uv_offset as *const std::ffi::c_void,
+            );
+
+            gl::EnableVertexAttribArray(2);
+            gl::VertexAttribPointer(
+                2,
+                3,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                normal_offset as *const std::ffi::c_void,
+            );
+
+            if self.has_tangents {
+                gl::EnableVertexAttribArray(3);
+                gl::VertexAttribPointer(
+                    3,
+                    4,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    stride,
+                    tangent_offset as *const std::ffi::c_void,
+                );
+            } else {
+                gl::DisableVertexAttribArray(3);
+            }
+        }
+
+        unsafe {
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.index_buffer);
+            gl::DrawElements(
+                gl::TRIANGLES,
+                self.index_count,
+                self.index_type,
+                std::ptr::null(),
+            );
+        }
+    }
+
+    pub fn delete(&self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.vertex_buffer);
+            gl::DeleteBuffers(1, &self.index_buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_unit_sphere(mesh: &Mesh, max_u: f32) {
+        for vertex in &mesh.vertices {
+            assert!((glm::length(vertex.normal) - 1.0).abs() < 1e-5);
+            assert!((glm::length(vertex.tangent.truncate(3)) - 1.0).abs() < 1e-5);
+            assert!(glm::dot(vertex.normal, vertex.tangent.truncate(3)).abs() < 1e-5);
+            assert!(vertex.uv.x >= 0.0 && vertex.uv.x <= max_u);
+            assert!(vertex.uv.y >= 0.0 && vertex.uv.y <= 1.0);
+        }
+
+        for triangle in 0..mesh.triangle_count() {
+            let corners: Vec<Vertex> = (0..3)
+                .map(|corner| mesh.vertices[mesh.indices.get(triangle * 3 + corner)])
+                .collect();
+            let (a, b, c) = (corners[0], corners[1], corners[2]);
+
+            // counter-clockwise from outside and no wrapping across the seam
+            let face_normal = glm::cross(b.position - a.position, c.position - a.position);
+            assert!(glm::dot(face_normal, a.normal + b.normal + c.normal) > 0.0);
+            assert!((a.uv.x - b.uv.x).abs() < 0.5 && (a.uv.x - c.uv.x).abs() < 0.5);
+        }
+    }
+
+    fn cube() -> Mesh {
+        let vertices = (0..8)
+            .map(|i| Vertex {
+                position: glm::vec3(
+                    if i & 1 == 0 { -1.0 } else { 1.0 },
+                    if i & 2 == 0 { -1.0 } else { 1.0 },
+                    if i & 4 == 0 { -1.0 } else { 1.0 },
+                ),
+                uv: glm::vec2(0.0, 0.0),
+                normal: glm::vec3(0.0, 0.0, 0.0),
+                tangent: glm::vec4(0.0, 0.0, 0.0, 0.0),
+            })
+            .collect();
+        let quads = [
+            [0, 4, 6, 2],
+            [1, 3, 7, 5],
+            [0, 1, 5, 4],
+            [2, 6, 7, 3],
+            [0, 2, 3, 1],
+            [4, 5, 7, 6],
+        ];
+        let indices = quads
+            .iter()
+            .flat_map(|&[a, b, c, d]| vec![a, b, c, a, c, d])
+            .collect();
+
+        Mesh {
+            vertices,
+            indices: Indices::new(indices, 8),
+        }
+    }
+
+    #[test]
+    fn generated_normals_keep_creases() {
+        let mut mesh = cube();
+        mesh.generate_normals(PI / 3.0);
+
+        // every corner is split into one vertex per side
+        assert_eq!(mesh.vertices.len(), 24);
+        for vertex in &mesh.vertices {
+            let n = vertex.normal;
+            assert_eq!(n.x.abs() + n.y.abs() + n.z.abs(), 1.0);
+        }
+    }
+
+    #[test]
+    fn generated_normals_smooth_below_the_crease_angle() {
+        let mut mesh = cube();
+        mesh.generate_normals(PI);
+
+        assert_eq!(mesh.vertices.len(), 8);
+        let expected = glm::normalize(glm::vec3(-1.0, -1.0, -1.0));
+        assert!(glm::length(mesh.vertices[0].normal - expected) < 1e-6);
+
+        // the seam column of a sphere is welded, so no hard edge shows there
+        let mut sphere = sphere(8, 16);
+        sphere.generate_normals(PI / 3.0);
+
+        // minus the one unused vertex at each pole
+        assert_eq!(sphere.vertices.len(), 9 * 17 - 2);
+        for vertex in &sphere.vertices {
+            assert!(glm::dot(vertex.normal, vertex.position) > 0.99);
+        }
+    }
+
+    /// Unit quad in the XY plane facing +Z, with `u` running along `u_axis`.
+    fn quad(u_axis: f32) -> Mesh {
+        let vertices = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]
+            .iter()
+            .map(|&(x, y)| Vertex {
+                position: glm::vec3(x, y, 0.0),
+                uv: glm::vec2(x * u_axis, y),
+                normal: glm::vec3(0.0, 0.0, 1.0),
+                tangent: glm::vec4(0.0, 0.0, 0.0, 0.0),
+            })
+            .collect();
+
+        Mesh {
+            vertices,
+            indices: Indices::new(vec![0, 1, 2, 0, 2, 3], 4),
+        }
+    }
+
+    #[test]
+    fn generated_tangents_follow_the_uvs() {
+        let mut mesh = quad(1.0);
+        assert!(mesh.generate_tangents());
+
+        assert_eq!(mesh.vertices.len(), 4);
+        for vertex in &mesh.vertices {
+            assert!(glm::length(vertex.tangent - glm::vec4(1.0, 0.0, 0.0, 1.0)) < 1e-6);
+        }
+
+        // mirrored texture: the tangent flips and the bitangent still
+        // points along +v
+        let mut mirrored = quad(-1.0);
+        assert!(mirrored.generate_tangents());
+
+        for vertex in &mirrored.vertices {
+            assert!(glm::length(vertex.tangent - glm::vec4(-1.0, 0.0, 0.0, -1.0)) < 1e-6);
+            let t = vertex.tangent;
+            let bitangent = glm::cross(vertex.normal, t.truncate(3)) * t.w;
+            assert!(glm::length(bitangent - glm::vec3(0.0, 1.0, 0.0)) < 1e-6);
+        }
+    }
+
+    #[test]
+    fn spherical_uvs_match_the_uv_sphere() {
+        let original = sphere(8, 16);
+        let mut mesh = original.clone();
+        mesh.generate_spherical_uvs();
+
+        assert_eq!(mesh.triangle_count(), original.triangle_count());
+        for i in 0..mesh.indices.len() {
+            let (a, b) = (
+                original.vertices[original.indices.get(i)],
+                mesh.vertices[mesh.indices.get(i)],
+            );
+            assert!((a.uv.y - b.uv.y).abs() < 1e-5);
+            // poles and the seam are only known up to a full turn
+            let is_pole = a.uv.y.abs() < 1e-5 || (a.uv.y - 1.0).abs() < 1e-5;
+            if !is_pole {
+                assert!((a.uv.x - b.uv.x).abs() < 1e-5 || (a.uv.x - b.uv.x).abs() > 1.0 - 1e-5);
+            }
+        }
+
+        // no triangle wraps back across the seam
+        for triangle in 0..mesh.triangle_count() {
+            let u: Vec<f32> = (0..3)
+                .map(|i| mesh.vertices[mesh.indices.get(triangle * 3 + i)].uv.x)
+                .collect();
+            let spread = u.iter().cloned().fold(f32::MIN, f32::max)
+                - u.iter().cloned().fold(f32::MAX, f32::min);
+            assert!(spread <= 1.0 / 16.0 + 1e-5);
+        }
+    }
+
+    #[test]
+    fn flat_smoothing_group() {
+        let positions = [
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(1.0, 0.0, 0.0),
+            glm::vec3(0.0, 1.0, 0.0),
+            glm::vec3(0.0, 0.0, 1.0),
+        ];
+        let triangles = [[0, 1, 2], [0, 3, 1]];
+
+        let smooth = corner_normals(&positions, &triangles, &[1, 1], PI);
+        let flat = corner_normals(&positions, &triangles, &[0, 0], PI);
+        let split = corner_normals(&positions, &triangles, &[1, 2], PI);
+
+        let blended = glm::normalize(glm::vec3(0.0, 1.0, 1.0));
+        assert!(glm::length(smooth[0][0] - blended) < 1e-6);
+        assert!(glm::length(smooth[0][2] - glm::vec3(0.0, 0.0, 1.0)) < 1e-6);
+        assert_eq!(flat[0], [glm::vec3(0.0, 0.0, 1.0); 3]);
+        assert_eq!(split, flat);
+    }
+
+    #[test]
+    fn sphere_counts() {
+        let mesh = sphere(32, 64);
+
+        assert_eq!(mesh.vertices.len(), 33 * 65);
+        assert_eq!(mesh.triangle_count(), 2 * 64 * 31);
+        assert_unit_sphere(&mesh, 1.0);
+    }
+
+    #[test]
+    fn sphere_seam_and_poles() {
+        let mesh = sphere(4, 8);
+
+        let north = mesh.vertices[0];
+        assert_eq!(north.position.y, 1.0);
+        assert_eq!(north.uv.y, 1.0);
+
+        // first and last column share positions but not u
+        let (first, last) = (mesh.vertices[9], mesh.vertices[17]);
+        assert!(glm::length(first.position - last.position) < 1e-5);
+        assert_eq!((first.uv.x, last.uv.x), (0.0, 1.0));
+    }
+
+    #[test]
+    fn icosphere_counts() {
+        for subdivisions in 0..4 {
+            let mesh = icosphere(subdivisions);
+            let faces = 20 * 4usize.pow(subdivisions as u32);
+
+            assert_eq!(mesh.triangle_count(), faces);
+            // 10 * 4^n + 2 distinct points, plus copies along the seam and poles
+            assert!(mesh.vertices.len() > faces / 2 + 2);
+            assert_unit_sphere(&mesh, 1.5);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use crate::bounds::Bounds;
+use crate::lod;
+use crate::material::Material;
+use crate::mesh::{GpuMesh, Mesh};
+
+/// Named part of a model, drawn with a single material (an index into
+/// `Model::materials`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubMesh {
+    pub name: String,
+    pub material: Option<usize>,
+    pub mesh: Mesh,
+    pub visible: bool,
+}
+
+/// Everything loaded from one model file: its parts and the materials they
+/// reference.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Model {
+    pub meshes: Vec<SubMesh>,
+    pub materials: Vec<Material>,
+}
+
+impl Model {
+    /// Wraps a single mesh, e.g. a generated one, into a model of one part.
+    pub fn from_mesh(name: &str, mesh: Mesh, material: Option<Material>) -> Model {
+        Model {
+            meshes: vec![SubMesh {
+                name: name.to_string(),
+                material: material.as_ref().map(|_| 0),
+                mesh,
+                visible: true,
+            }],
+            materials: material.into_iter().collect(),
+        }
+    }
+
+    pub fn find(&self, name: &str) -> Option<&SubMesh> {
+        self.meshes.iter().find(|sub_mesh| sub_mesh.name == name)
+    }
+
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut SubMesh> {
+        self.meshes
+            .iter_mut()
+            .find(|sub_mesh| sub_mesh.name == name)
+    }
+
+    /// Shows or hides the part called `name`, returns false if there is none.
+    pub fn set_visible(&mut self, name: &str, visible: bool) -> bool {
+        match self.find_mut(name) {
+            Some(sub_mesh) => {
+                sub_mesh.visible = visible;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn material_of(&self, sub_mesh: &SubMesh) -> Option<&Material> {
+        sub_mesh.material.map(|material| &self.materials[material])
+    }
+
+    /// Volumes around every part, visible or not.
+    pub fn bounds(&self) -> Bounds {
+        Bounds::from_points(
+            self.meshes
+                .iter()
+                .flat_map(|sub_mesh| &sub_mesh.mesh.vertices)
+                .map(|vertex| &vertex.position),
+        )
+    }
+
+    /// Simplified copies of the model, level 0 being the model itself (see
+    /// `lod::chain`). Parts that run out of levels early repeat their
+    /// coarsest one, so every level has all the parts.
+    pub fn lods(&self, max_levels: usize) -> Vec<Model> {
+        let chains: Vec<Vec<Mesh>> = self
+            .meshes
+            .iter()
+            .map(|sub_mesh| lod::chain(&sub_mesh.mesh, max_levels))
+            .collect();
+        let level_count = chains.iter().map(Vec::len).max().unwrap_or(1);
+
+        (0..level_count)
+            .map(|level| Model {
+                meshes: self
+                    .meshes
+                    .iter()
+                    .zip(&chains)
+                    .map(|(sub_mesh, chain)| SubMesh {
+                        name: sub_mesh.name.clone(),
+                        material: sub_mesh.material,
+                        mesh: chain[level.min(chain.len() - 1)].clone(),
+                        visible: sub_mesh.visible,
+                    })
+                    .collect(),
+                materials: self.materials.clone(),
+            })
+            .collect()
+    }
+
+    /// Uploads every part, in the same order as `meshes`.
+    pub fn upload(&self) -> Vec<GpuMesh> {
+        self.meshes
+            .iter()
+            .map(|sub_mesh| GpuMesh::upload(&sub_mesh.mesh))
+            .collect()
+    }
+}
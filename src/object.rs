@@ -1,120 +1,1035 @@
+use crate::material;
+use crate::mesh::{self, Indices, Mesh, Vertex};
+use crate::model::{Model, SubMesh};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
 
-macro_rules! scan {
-    ( $string:expr, $sep:expr, $( $x:ty ),+ ) => {{
-        let mut iter = $string.split($sep);
-        ($(iter.next().and_then(|word| word.parse::<$x>().ok()),)*)
-    }}
+#[derive(Debug)]
+pub enum ObjErrorKind {
+    Io(io::Error),
+    BadFloat,
+    BadIndex,
+    IndexOutOfRange,
+    MissingComponent,
+    UnsupportedDirective,
+    UnknownMaterial,
 }
 
-pub fn load(
-    path: &str,
-) -> (
-    Vec<glm::Vector3<f32>>,
-    Vec<glm::Vector2<f32>>,
-    Vec<glm::Vector3<f32>>,
-) {
-    let file = File::open(path).unwrap();
-    let reader = BufReader::new(file);
+#[derive(Debug)]
+pub struct ObjError {
+    pub file: String,
+    pub line: usize,
+    pub token: String,
+    pub kind: ObjErrorKind,
+}
+
+impl fmt::Display for ObjErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjErrorKind::Io(err) => write!(f, "{}", err),
+            ObjErrorKind::BadFloat => write!(f, "invalid float"),
+            ObjErrorKind::BadIndex => write!(f, "invalid index"),
+            ObjErrorKind::IndexOutOfRange => write!(f, "index out of range"),
+            ObjErrorKind::MissingComponent => write!(f, "missing component"),
+            ObjErrorKind::UnsupportedDirective => write!(f, "unsupported directive"),
+            ObjErrorKind::UnknownMaterial => write!(f, "unknown material"),
+        }
+    }
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            return write!(f, "{}: {}", self.file, self.kind);
+        }
+
+        write!(f, "{}:{}: {}", self.file, self.line, self.kind)?;
+
+        if !self.token.is_empty() {
+            write!(f, " '{}'", self.token)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for ObjError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.kind {
+            ObjErrorKind::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Tunables for `load_with` and `parse_with`.
+#[derive(Clone, Copy, Debug)]
+pub struct LoadOptions {
+    /// Faces meeting at a sharper angle than this (in radians) keep a hard
+    /// edge between them when normals are generated.
+    pub crease_angle: f32,
+}
+
+impl Default for LoadOptions {
+    fn default() -> LoadOptions {
+        LoadOptions {
+            crease_angle: 60f32.to_radians(),
+        }
+    }
+}
+
+pub fn load(path: &str) -> Result<Model, ObjError> {
+    load_with(path, &LoadOptions::default())
+}
+
+pub fn load_with(path: &str, options: &LoadOptions) -> Result<Model, ObjError> {
+    let file = File::open(path).map_err(|err| ObjError {
+        file: path.to_string(),
+        line: 0,
+        token: String::new(),
+        kind: ObjErrorKind::Io(err),
+    })?;
+
+    parse_with(BufReader::new(file), path, options)
+}
+
+pub fn parse<R: BufRead>(reader: R, file: &str) -> Result<Model, ObjError> {
+    parse_with(reader, file, &LoadOptions::default())
+}
 
-    let (mut vertices, mut uvs, mut normals) = (vec![], vec![], vec![]);
+/// Parses OBJ data from any reader, `file` labels errors and is where
+/// `mtllib` paths are resolved from.
+///
+/// Faces are split into one sub-mesh per object/group and material, named
+/// `object` or `object/group`. Within a sub-mesh, corners sharing the same
+/// position/uv/normal triplet become one vertex. Faces written without UVs get
+/// zeroed ones.
+///
+/// Faces written without normals get area-weighted ones, smoothed across
+/// faces of the same `s` group up to `options.crease_angle`; `s off` faces
+/// are flat. A file without any `s` statement is smoothed as one group.
+pub fn parse_with<R: BufRead>(
+    reader: R,
+    file: &str,
+    options: &LoadOptions,
+) -> Result<Model, ObjError> {
+    let directory = Path::new(file).parent().unwrap_or_else(|| Path::new(""));
+    let mut materials = vec![];
+    let mut builders: Vec<SubMeshBuilder> = vec![];
 
-    let (mut vertex_indices, mut uv_indices, mut normal_indices): (
-        Vec<usize>,
-        Vec<usize>,
-        Vec<usize>,
-    ) = (vec![], vec![], vec![]);
+    let (mut object, mut group) = (String::from("default"), None);
+    let mut current_material = None;
+    let mut smoothing_group = None;
 
     let (mut temp_vertices, mut temp_uvs, mut temp_normals) = (vec![], vec![], vec![]);
 
-    for line in reader.lines().map(Result::unwrap) {
-        // just straight up bad code e.e
-        let line_clone = line.clone();
-        let line_type = line_clone.split_whitespace().next().unwrap();
-        let line: String = line
-            .split_whitespace()
-            .skip(1)
-            .map(|s| format!("{}\n", s))
+    for (line_index, line) in reader.lines().enumerate() {
+        let context = Context {
+            file,
+            line: line_index + 1,
+        };
+
+        let line = line.map_err(|err| context.error("", ObjErrorKind::Io(err)))?;
+        // everything after a '#' is a comment
+        let line = line.split('#').next().unwrap_or_default();
+        let mut tokens = line.split_whitespace();
+
+        let line_type = match tokens.next() {
+            Some(line_type) => line_type,
+            None => continue,
+        };
+
+        match line_type {
+            "v" => {
+                let (x, y, z) = (
+                    context.float(tokens.next())?,
+                    context.float(tokens.next())?,
+                    context.float(tokens.next())?,
+                );
+
+                temp_vertices.push(glm::vec3(x, y, z));
+            }
+            "vt" => {
+                let (x, y) = (context.float(tokens.next())?, context.float(tokens.next())?);
+
+                temp_uvs.push(glm::vec2(x, y));
+            }
+            "vn" => {
+                let (x, y, z) = (
+                    context.float(tokens.next())?,
+                    context.float(tokens.next())?,
+                    context.float(tokens.next())?,
+                );
+
+                temp_normals.push(glm::vec3(x, y, z));
+            }
+            "f" => {
+                let mut corners = vec![];
+
+                for group in tokens {
+                    corners.push(context.corner(
+                        group,
+                        temp_vertices.len(),
+                        temp_uvs.len(),
+                        temp_normals.len(),
+                    )?);
+                }
+
+                if corners.len() < 3 {
+                    return Err(context.error("", ObjErrorKind::MissingComponent));
+                }
+
+                let polygon: Vec<_> = corners
+                    .iter()
+                    .map(|corner| temp_vertices[corner.vertex])
+                    .collect();
+
+                let name = match &group {
+                    Some(group) => format!("{}/{}", object, group),
+                    None => object.clone(),
+                };
+
+                let builder = match builders.iter().position(|builder| {
+                    builder.name == name && builder.material == current_material
+                }) {
+                    Some(i) => &mut builders[i],
+                    None => {
+                        builders.push(SubMeshBuilder {
+                            name,
+                            material: current_material,
+                            triangles: vec![],
+                            smoothing_groups: vec![],
+                        });
+                        builders.last_mut().unwrap()
+                    }
+                };
+
+                for [a, b, c] in triangulate(&polygon) {
+                    builder.triangles.push([corners[a], corners[b], corners[c]]);
+                    builder.smoothing_groups.push(smoothing_group);
+                }
+            }
+            "mtllib" => {
+                for name in tokens {
+                    let path = directory.join(name).to_string_lossy().into_owned();
+                    let library = material::load(&path).map_err(|err| match err.kind {
+                        // point at the mtllib line when the library itself is missing
+                        ObjErrorKind::Io(_) if err.line == 0 => context.error(name, err.kind),
+                        _ => err,
+                    })?;
+
+                    materials.extend(library);
+                }
+            }
+            "usemtl" => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                let material = materials
+                    .iter()
+                    .position(|material: &material::Material| material.name == name)
+                    .ok_or_else(|| context.error(&name, ObjErrorKind::UnknownMaterial))?;
+
+                current_material = Some(material);
+            }
+            "o" => {
+                object = tokens.collect::<Vec<_>>().join(" ");
+                group = None;
+            }
+            "g" => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                group = if name.is_empty() { None } else { Some(name) };
+            }
+            "s" => {
+                let token = tokens.next().unwrap_or_default();
+
+                smoothing_group = Some(match token {
+                    "off" => 0,
+                    _ => token
+                        .parse::<u32>()
+                        .map_err(|_| context.error(token, ObjErrorKind::BadIndex))?,
+                });
+            }
+            _ => return Err(context.error(line_type, ObjErrorKind::UnsupportedDirective)),
+        }
+    }
+
+    let meshes = builders
+        .into_iter()
+        .map(|builder| SubMesh {
+            name: builder.name.clone(),
+            material: builder.material,
+            mesh: builder.build(&temp_vertices, &temp_uvs, &temp_normals, options),
+            visible: true,
+        })
+        .collect();
+
+    Ok(Model { meshes, materials })
+}
+
+/// Writes `model` as an OBJ file at `path`, with its materials in an MTL
+/// library next to it (same name, `.mtl` extension).
+///
+/// Each part becomes an `o`/`g` pair named after it, with its own `v`, `vt`
+/// and `vn` lines. Parts without a material are written first, since OBJ
+/// can't switch back to no material once `usemtl` was used.
+pub fn save(model: &Model, path: &str) -> Result<(), ObjError> {
+    let io_error = |file: &str, err| ObjError {
+        file: file.to_string(),
+        line: 0,
+        token: String::new(),
+        kind: ObjErrorKind::Io(err),
+    };
+
+    let library = if model.materials.is_empty() {
+        None
+    } else {
+        let library = Path::new(path).with_extension("mtl");
+        let library_path = library.to_string_lossy().into_owned();
+        material::save(&model.materials, &library_path)?;
+
+        library
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+    };
+
+    let file = File::create(path).map_err(|err| io_error(path, err))?;
+    let mut writer = BufWriter::new(file);
+
+    write(model, &mut writer, library.as_deref())
+        .and_then(|_| writer.flush())
+        .map_err(|err| io_error(path, err))
+}
+
+/// Saves a single mesh, e.g. a generated or simplified one, without
+/// materials.
+pub fn save_mesh(mesh: &Mesh, path: &str) -> Result<(), ObjError> {
+    save(&Model::from_mesh("default", mesh.clone(), None), path)
+}
+
+/// Writes `model` as OBJ text, referencing `library` with `mtllib` if given.
+pub fn write<W: Write>(model: &Model, writer: &mut W, library: Option<&str>) -> io::Result<()> {
+    writeln!(writer, "# solar-system OBJ export")?;
+
+    if let Some(library) = library {
+        writeln!(writer, "mtllib {}", library)?;
+    }
+
+    let parts = model
+        .meshes
+        .iter()
+        .filter(|sub_mesh| sub_mesh.material.is_none())
+        .chain(
+            model
+                .meshes
+                .iter()
+                .filter(|sub_mesh| sub_mesh.material.is_some()),
+        );
+
+    // OBJ indices are 1-based and count across the whole file
+    let mut offset = 1;
+
+    for sub_mesh in parts {
+        let mesh = &sub_mesh.mesh;
+
+        writeln!(writer)?;
+        match sub_mesh.name.split_once('/') {
+            Some((object, group)) => writeln!(writer, "o {}\ng {}", object, group)?,
+            None => writeln!(writer, "o {}", sub_mesh.name)?,
+        }
+
+        if let Some(material) = model.material_of(sub_mesh) {
+            writeln!(writer, "usemtl {}", material.name)?;
+        }
+
+        for vertex in &mesh.vertices {
+            let p = vertex.position;
+            writeln!(writer, "v {} {} {}", p.x, p.y, p.z)?;
+        }
+        for vertex in &mesh.vertices {
+            writeln!(writer, "vt {} {}", vertex.uv.x, vertex.uv.y)?;
+        }
+        for vertex in &mesh.vertices {
+            let n = vertex.normal;
+            writeln!(writer, "vn {} {} {}", n.x, n.y, n.z)?;
+        }
+
+        for triangle in 0..mesh.triangle_count() {
+            write!(writer, "f")?;
+            for corner in 0..3 {
+                let index = mesh.indices.get(triangle * 3 + corner) + offset;
+                write!(writer, " {0}/{0}/{0}", index)?;
+            }
+            writeln!(writer)?;
+        }
+
+        offset += mesh.vertices.len();
+    }
+
+    Ok(())
+}
+
+/// The triangles of one sub-mesh, with their smoothing group (`None` until
+/// the file has an `s` statement).
+struct SubMeshBuilder {
+    name: String,
+    material: Option<usize>,
+    triangles: Vec<[Corner; 3]>,
+    smoothing_groups: Vec<Option<u32>>,
+}
+
+/// Where a vertex's normal comes from, generated ones are told apart by value
+/// so creases split vertices.
+#[derive(Clone, Copy, Hash, PartialEq, Eq)]
+enum NormalSource {
+    Given(usize),
+    Generated([u32; 3]),
+}
+
+impl SubMeshBuilder {
+    /// Turns the triangles into an indexed mesh, corners sharing the same
+    /// position/uv/normal become one vertex.
+    fn build(
+        &self,
+        positions: &[glm::Vector3<f32>],
+        uvs: &[glm::Vector2<f32>],
+        normals: &[glm::Vector3<f32>],
+        options: &LoadOptions,
+    ) -> Mesh {
+        let needs_normals = self
+            .triangles
+            .iter()
+            .flatten()
+            .any(|corner| corner.normal.is_none());
+
+        let generated = if needs_normals {
+            let triangles: Vec<_> = self
+                .triangles
+                .iter()
+                .map(|[a, b, c]| [a.vertex, b.vertex, c.vertex])
+                .collect();
+            let groups: Vec<_> = self
+                .smoothing_groups
+                .iter()
+                .map(|group| group.unwrap_or(1))
+                .collect();
+
+            mesh::corner_normals(positions, &triangles, &groups, options.crease_angle)
+        } else {
+            vec![]
+        };
+
+        let mut vertices = vec![];
+        let mut indices = vec![];
+        let mut unique_vertices = HashMap::new();
+
+        for (face, triangle) in self.triangles.iter().enumerate() {
+            for (i, corner) in triangle.iter().enumerate() {
+                let (normal, source) = match corner.normal {
+                    Some(normal) => (normals[normal], NormalSource::Given(normal)),
+                    None => {
+                        let normal = generated[face][i];
+                        (normal, NormalSource::Generated(mesh::bits(normal)))
+                    }
+                };
+
+                let index = *unique_vertices
+                    .entry((corner.vertex, corner.uv, source))
+                    .or_insert_with(|| {
+                        vertices.push(Vertex {
+                            position: positions[corner.vertex],
+                            uv: corner.uv.map_or(glm::vec2(0.0, 0.0), |uv| uvs[uv]),
+                            normal,
+                            tangent: glm::vec4(0.0, 0.0, 0.0, 0.0),
+                        });
+
+                        (vertices.len() - 1) as u32
+                    });
+
+                indices.push(index);
+            }
+        }
+
+        let mut mesh = Mesh {
+            indices: Indices::new(indices, vertices.len()),
+            vertices,
+        };
+
+        // without UVs there is no texture space to build tangents in
+        if self
+            .triangles
+            .iter()
+            .flatten()
+            .any(|corner| corner.uv.is_some())
+        {
+            mesh.generate_tangents();
+        }
+
+        mesh
+    }
+}
+
+/// Where in the file we are, so errors can point at the offending line.
+pub(crate) struct Context<'a> {
+    pub(crate) file: &'a str,
+    pub(crate) line: usize,
+}
+
+impl<'a> Context<'a> {
+    pub(crate) fn error(&self, token: &str, kind: ObjErrorKind) -> ObjError {
+        ObjError {
+            file: self.file.to_string(),
+            line: self.line,
+            token: token.to_string(),
+            kind,
+        }
+    }
+
+    pub(crate) fn float(&self, token: Option<&str>) -> Result<f32, ObjError> {
+        let token = token.ok_or_else(|| self.error("", ObjErrorKind::MissingComponent))?;
+
+        token
+            .parse::<f32>()
+            .map_err(|_| self.error(token, ObjErrorKind::BadFloat))
+    }
+
+    /// Parses one `v`, `v/vt`, `v//vn` or `v/vt/vn` group of a face.
+    fn corner(
+        &self,
+        group: &str,
+        vertex_count: usize,
+        uv_count: usize,
+        normal_count: usize,
+    ) -> Result<Corner, ObjError> {
+        let group_str: Vec<&str> = group.split('/').collect();
+
+        match group_str.as_slice() {
+            [v] => Ok(Corner {
+                vertex: self.index(group, v, vertex_count)?,
+                uv: None,
+                normal: None,
+            }),
+            [v, vt] => Ok(Corner {
+                vertex: self.index(group, v, vertex_count)?,
+                uv: Some(self.index(group, vt, uv_count)?),
+                normal: None,
+            }),
+            [v, vt, vn] => Ok(Corner {
+                vertex: self.index(group, v, vertex_count)?,
+                uv: if vt.is_empty() {
+                    None
+                } else {
+                    Some(self.index(group, vt, uv_count)?)
+                },
+                normal: Some(self.index(group, vn, normal_count)?),
+            }),
+            _ => Err(self.error(group, ObjErrorKind::BadIndex)),
+        }
+    }
+
+    /// Converts a 1-based (or negative, relative to the end) OBJ index into a
+    /// 0-based one, checking it against the `count` elements declared so far.
+    fn index(&self, group: &str, token: &str, count: usize) -> Result<usize, ObjError> {
+        if token.is_empty() {
+            return Err(self.error(group, ObjErrorKind::MissingComponent));
+        }
+
+        let index = token
+            .parse::<isize>()
+            .map_err(|_| self.error(token, ObjErrorKind::BadIndex))?;
+
+        let index = if index < 0 {
+            count as isize + index
+        } else {
+            index - 1
+        };
+
+        if index < 0 || index >= count as isize {
+            return Err(self.error(token, ObjErrorKind::IndexOutOfRange));
+        }
+
+        Ok(index as usize)
+    }
+}
+
+/// Indices of one face corner into the position, uv and normal lists.
+#[derive(Clone, Copy)]
+struct Corner {
+    vertex: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+/// Splits a polygon into triangles by ear clipping, so concave faces come out
+/// right too. Returns indices into `polygon`.
+pub(crate) fn triangulate(polygon: &[glm::Vector3<f32>]) -> Vec<[usize; 3]> {
+    if polygon.len() == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // Newell's method gives a stable normal even for concave polygons
+    let mut normal = glm::vec3(0.0, 0.0, 0.0);
+    for (i, current) in polygon.iter().enumerate() {
+        let next = polygon[(i + 1) % polygon.len()];
+        normal.x += (current.y - next.y) * (current.z + next.z);
+        normal.y += (current.z - next.z) * (current.x + next.x);
+        normal.z += (current.x - next.x) * (current.y + next.y);
+    }
+
+    let mut remaining: Vec<usize> = (0..polygon.len()).collect();
+    let mut triangles = vec![];
+
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|&i| {
+            let (a, b, c) = (
+                remaining[(i + count - 1) % count],
+                remaining[i],
+                remaining[(i + 1) % count],
+            );
+
+            is_ear(polygon, &remaining, normal, a, b, c)
+        });
+
+        // degenerate polygon, fall back to a fan over what is left
+        let i = match ear {
+            Some(i) => i,
+            None => break,
+        };
+
+        triangles.push([
+            remaining[(i + count - 1) % count],
+            remaining[i],
+            remaining[(i + 1) % count],
+        ]);
+        remaining.remove(i);
+    }
+
+    for i in 1..remaining.len() - 1 {
+        triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+    }
+
+    triangles
+}
+
+fn is_ear(
+    polygon: &[glm::Vector3<f32>],
+    remaining: &[usize],
+    normal: glm::Vector3<f32>,
+    a: usize,
+    b: usize,
+    c: usize,
+) -> bool {
+    let (pa, pb, pc) = (polygon[a], polygon[b], polygon[c]);
+
+    // reflex corners can't be ears
+    if glm::dot(glm::cross(pb - pa, pc - pb), normal) <= 0.0 {
+        return false;
+    }
+
+    let inside = |p: glm::Vector3<f32>| {
+        glm::dot(glm::cross(pb - pa, p - pa), normal) >= 0.0
+            && glm::dot(glm::cross(pc - pb, p - pb), normal) >= 0.0
+            && glm::dot(glm::cross(pa - pc, p - pc), normal) >= 0.0
+    };
+
+    remaining
+        .iter()
+        .filter(|&&i| i != a && i != b && i != c)
+        .all(|&i| !inside(polygon[i]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRIANGLE: &str = "\
+v 0.0 0.0 0.0
+v 1.0 0.0 0.0
+v 0.0 1.0 0.0
+vt 0.0 0.0
+vt 1.0 0.0
+vt 0.0 1.0
+vn 0.0 0.0 1.0
+";
+
+    fn parse_str(source: &str) -> Result<Model, ObjError> {
+        parse(source.as_bytes(), "test.obj")
+    }
+
+    /// Parses a file expected to hold a single sub-mesh.
+    fn parse_mesh(source: &str) -> Mesh {
+        let mut model = parse_str(source).unwrap();
+
+        assert_eq!(model.meshes.len(), 1);
+        model.meshes.remove(0).mesh
+    }
+
+    fn corner(mesh: &Mesh, i: usize) -> Vertex {
+        mesh.vertices[mesh.indices.get(i)]
+    }
+
+    #[test]
+    fn parses_a_triangle() {
+        let source = format!("{}f 1/1/1 2/2/1 3/3/1\n", TRIANGLE);
+        let mesh = parse_mesh(&source);
+
+        assert_eq!(mesh.vertices.len(), 3);
+        assert_eq!(mesh.triangle_count(), 1);
+        assert_eq!(corner(&mesh, 1).position.x, 1.0);
+        assert_eq!(corner(&mesh, 2).uv.y, 1.0);
+        assert_eq!(corner(&mesh, 0).normal.z, 1.0);
+    }
+
+    #[test]
+    fn parses_every_face_form() {
+        let source = format!(
+            "{}f 1 2 3\nf 1/1 2/2 3/3\nf 1//1 2//1 3//1\nf -3/-3/-1 -2/-2/-1 -1/-1/-1\n",
+            TRIANGLE
+        );
+        let mesh = parse_mesh(&source);
+
+        assert_eq!(mesh.triangle_count(), 4);
+        assert_eq!(corner(&mesh, 0).uv.x, 0.0);
+        // generated from the face, since the file gives none
+        assert_eq!(corner(&mesh, 0).normal.z, 1.0);
+        assert_eq!(corner(&mesh, 4).uv.x, 1.0);
+        assert_eq!(corner(&mesh, 7).normal.z, 1.0);
+        assert_eq!(corner(&mesh, 11).position.y, 1.0);
+        assert_eq!(corner(&mesh, 10).uv.x, 1.0);
+    }
+
+    /// Two quads folded 90 degrees along the x axis, sharing vertices 1 and 2.
+    const FOLD: &str = "\
+v 0 0 1
+v 0 0 0
+v 1 0 0
+v 1 0 1
+v 0 1 0
+v 1 1 0
+";
+
+    #[test]
+    fn generates_smooth_normals() {
+        let options = LoadOptions {
+            crease_angle: 120f32.to_radians(),
+        };
+        let source = format!(
+            "{}f 1 4 3 2
+f 2 3 6 5
+",
+            FOLD
+        );
+        let model = parse_with(source.as_bytes(), "test.obj", &options).unwrap();
+        let mesh = &model.meshes[0].mesh;
+
+        // the fold's vertices are shared and bent halfway between the faces
+        assert_eq!(mesh.vertices.len(), 6);
+        let fold = mesh
+            .vertices
+            .iter()
+            .find(|vertex| vertex.position == glm::vec3(0.0, 0.0, 0.0))
+            .unwrap();
+        assert!((glm::length(fold.normal) - 1.0).abs() < 1e-6);
+        assert!(fold.normal.y > 0.1 && fold.normal.z > 0.1 && fold.normal.x == 0.0);
+    }
+
+    #[test]
+    fn keeps_hard_edges() {
+        // sharper than the default crease angle
+        let sharp = parse_mesh(&format!(
+            "{}f 1 4 3 2
+f 2 3 6 5
+",
+            FOLD
+        ));
+        // different smoothing groups
+        let groups = parse_mesh(&format!(
+            "{}s 1
+f 1 4 3 2
+s 2
+f 2 3 6 5
+",
+            FOLD
+        ));
+        // smoothing turned off
+        let flat = parse_mesh(&format!(
+            "{}s off
+f 1 4 3 2
+f 2 3 6 5
+",
+            FOLD
+        ));
+
+        for mesh in &[sharp, groups, flat] {
+            assert_eq!(mesh.vertices.len(), 8);
+            for vertex in &mesh.vertices {
+                assert!(
+                    vertex.normal == glm::vec3(0.0, 1.0, 0.0)
+                        || vertex.normal == glm::vec3(0.0, 0.0, 1.0)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn reports_bad_smoothing_group() {
+        let err = parse_str("s on\n").unwrap_err();
+
+        assert_eq!(err.token, "on");
+        assert!(matches!(err.kind, ObjErrorKind::BadIndex));
+    }
+
+    #[test]
+    fn generates_tangents_when_there_are_uvs() {
+        let textured = parse_mesh(&format!("{}f 1/1 2/2 3/3\n", TRIANGLE));
+        let plain = parse_mesh(&format!("{}f 1 2 3\n", TRIANGLE));
+
+        for vertex in &textured.vertices {
+            assert_eq!(vertex.tangent, glm::vec4(1.0, 0.0, 0.0, 1.0));
+        }
+        for vertex in &plain.vertices {
+            assert_eq!(vertex.tangent, glm::vec4(0.0, 0.0, 0.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn triangulates_quads() {
+        let source = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n";
+        let mesh = parse_mesh(source);
+
+        assert_eq!(mesh.triangle_count(), 2);
+    }
+
+    #[test]
+    fn shares_repeated_corners() {
+        let source = format!(
+            "{}f 1/1/1 2/2/1 3/3/1\nf 2/2/1 3/3/1 1/1/1\nf 1 2 3\n",
+            TRIANGLE
+        );
+        let mesh = parse_mesh(&source);
+
+        assert_eq!(mesh.vertices.len(), 6);
+        assert_eq!(mesh.indices.len(), 9);
+        assert_eq!(mesh.indices.get(0), mesh.indices.get(5));
+        assert!(matches!(mesh.indices, Indices::U16(_)));
+    }
+
+    #[test]
+    fn triangulates_concave_polygons() {
+        // an arrow head, fanning from the first corner would cover the notch
+        let polygon = [
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(2.0, 1.0, 0.0),
+            glm::vec3(0.0, 2.0, 0.0),
+            glm::vec3(0.5, 1.0, 0.0),
+        ];
+        let triangles = triangulate(&polygon);
+
+        assert_eq!(triangles.len(), 2);
+        for triangle in triangles {
+            let (a, b, c) = (
+                polygon[triangle[0]],
+                polygon[triangle[1]],
+                polygon[triangle[2]],
+            );
+            assert!(glm::cross(b - a, c - a).z > 0.0);
+        }
+    }
+
+    #[test]
+    fn splits_by_material() {
+        let directory = std::env::temp_dir().join("solar-system-object-tests");
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(
+            directory.join("materials.mtl"),
+            "newmtl red\nKd 1 0 0\nnewmtl blue\nKd 0 0 1\nmap_Kd blue.png\n",
+        )
+        .unwrap();
+
+        let source = format!(
+            "mtllib materials.mtl\n{}f 1 2 3\nusemtl blue\nf 1 2 3\nusemtl red\nf 1 2 3\nusemtl blue\nf 3 2 1\n",
+            TRIANGLE
+        );
+        let file = directory.join("materials.obj");
+        let model = parse(source.as_bytes(), file.to_str().unwrap()).unwrap();
+
+        assert_eq!(model.materials.len(), 2);
+        assert_eq!(
+            model.materials[1].diffuse_map,
+            Some(directory.join("blue.png").to_string_lossy().into_owned())
+        );
+
+        let parts: Vec<_> = model
+            .meshes
+            .iter()
+            .map(|sub_mesh| (sub_mesh.material, sub_mesh.mesh.triangle_count()))
             .collect();
+        assert_eq!(parts, vec![(None, 1), (Some(1), 2), (Some(0), 1)]);
+        assert_eq!(model.material_of(&model.meshes[2]).unwrap().name, "red");
+    }
+
+    #[test]
+    fn splits_by_object_and_group() {
+        let source = format!(
+            "{}o Planet\nf 1 2 3\ng clouds\nf 1 2 3\no Ring\nf 1 2 3\ng\nf 3 2 1\n",
+            TRIANGLE
+        );
+        let mut model = parse_str(&source).unwrap();
+
+        let names: Vec<_> = model
+            .meshes
+            .iter()
+            .map(|sub_mesh| sub_mesh.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Planet", "Planet/clouds", "Ring"]);
+        assert_eq!(model.find("Ring").unwrap().mesh.triangle_count(), 2);
+
+        assert!(model.set_visible("Planet/clouds", false));
+        assert!(!model.find("Planet/clouds").unwrap().visible);
+        assert!(!model.set_visible("Moon", false));
+    }
+
+    #[test]
+    fn round_trips_through_save() {
+        let directory = std::env::temp_dir().join("solar-system-object-tests");
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let mut material = material::Material::new("earth");
+        material.diffuse_map = Some(directory.join("earth.png").to_string_lossy().into_owned());
+        let mut model = Model::from_mesh("Earth/surface", mesh::sphere(8, 16), Some(material));
+        model.meshes.push(SubMesh {
+            name: "Moon".to_string(),
+            material: None,
+            mesh: mesh::sphere(4, 8),
+            visible: true,
+        });
+
+        let file = directory.join("round_trip.obj");
+        save(&model, file.to_str().unwrap()).unwrap();
+        let loaded = load(file.to_str().unwrap()).unwrap();
+
+        assert_eq!(loaded.materials, model.materials);
+        let names: Vec<_> = loaded
+            .meshes
+            .iter()
+            .map(|sub_mesh| &sub_mesh.name)
+            .collect();
+        assert_eq!(names, vec!["Moon", "Earth/surface"]);
+
+        for (saved, loaded) in model.meshes.iter().rev().zip(&loaded.meshes) {
+            assert_eq!(saved.material, loaded.material);
+
+            let (saved, loaded) = (&saved.mesh, &loaded.mesh);
+            assert_eq!(saved.triangle_count(), loaded.triangle_count());
+            for i in 0..saved.indices.len() {
+                let a = saved.vertices[saved.indices.get(i)];
+                let b = loaded.vertices[loaded.indices.get(i)];
+
+                assert_eq!((a.position, a.uv, a.normal), (b.position, b.uv, b.normal));
+            }
+        }
+    }
+
+    #[test]
+    fn reports_unknown_material() {
+        let source = format!("{}usemtl nowhere\nf 1 2 3\n", TRIANGLE);
+        let err = parse_str(&source).unwrap_err();
+
+        assert_eq!(err.line, 8);
+        assert_eq!(err.token, "nowhere");
+        assert!(matches!(err.kind, ObjErrorKind::UnknownMaterial));
+    }
 
-        if line_type == "v" {
-            let (x, y, z) = scan!(line, char::is_whitespace, f32, f32, f32);
-
-            let x = x.unwrap();
-            let y = y.unwrap();
-            let z = z.unwrap();
-
-            temp_vertices.push(glm::vec3(x, y, z));
-        } else if line_type == "vt" {
-            let (x, y) = scan!(line, char::is_whitespace, f32, f32);
-
-            let x = x.unwrap();
-            let y = y.unwrap();
-
-            temp_uvs.push(glm::vec2(x, y));
-        } else if line_type == "vn" {
-            let (x, y, z) = scan!(line, char::is_whitespace, f32, f32, f32);
-
-            let x = x.unwrap();
-            let y = y.unwrap();
-            let z = z.unwrap();
-
-            temp_normals.push(glm::vec3(x, y, z));
-        } else if line_type == "f" {
-            let (mut vertex_index, mut uv_index, mut normal_index) =
-                ([0, 0, 0], [0, 0, 0], [0, 0, 0]);
-
-            let (group1, group2, group3) = scan!(line, char::is_whitespace, String, String, String);
-
-            let group1 = group1.unwrap();
-            let group1_str: Vec<&str> = group1.split('/').collect();
-            let n0 = group1_str[0].parse::<usize>().unwrap();
-            let n1 = group1_str[1].parse::<usize>().unwrap();
-            let n2 = group1_str[2].parse::<usize>().unwrap();
-            vertex_index[0] = n0;
-            uv_index[0] = n1;
-            normal_index[0] = n2;
-
-            let group2 = group2.unwrap();
-            let group2_str: Vec<&str> = group2.split('/').collect();
-            let n0 = group2_str[0].parse::<usize>().unwrap();
-            let n1 = group2_str[1].parse::<usize>().unwrap();
-            let n2 = group2_str[2].parse::<usize>().unwrap();
-            vertex_index[1] = n0;
-            uv_index[1] = n1;
-            normal_index[1] = n2;
-
-            let group3 = group3.unwrap();
-            let group3_str: Vec<&str> = group3.split('/').collect();
-            let n0 = group3_str[0].parse::<usize>().unwrap();
-            let n1 = group3_str[1].parse::<usize>().unwrap();
-            let n2 = group3_str[2].parse::<usize>().unwrap();
-            vertex_index[2] = n0;
-            uv_index[2] = n1;
-            normal_index[2] = n2;
-
-            vertex_indices.push(vertex_index[0]);
-            vertex_indices.push(vertex_index[1]);
-            vertex_indices.push(vertex_index[2]);
-            uv_indices.push(uv_index[0]);
-            uv_indices.push(uv_index[1]);
-            uv_indices.push(uv_index[2]);
-            normal_indices.push(normal_index[0]);
-            normal_indices.push(normal_index[1]);
-            normal_indices.push(normal_index[2]);
-        }
-    }
-
-    for i in 0..vertex_indices.len() {
-        let vertex_index = vertex_indices[i];
-        let uv_index = uv_indices[i];
-        let normal_index = normal_indices[i];
-
-        vertices.push(temp_vertices[vertex_index - 1]);
-        uvs.push(temp_uvs[uv_index - 1]);
-        normals.push(temp_normals[normal_index - 1]);
-    }
-
-    (vertices, uvs, normals)
+    #[test]
+    fn reports_missing_material_library() {
+        let err = parse_str("mtllib missing.mtl\n").unwrap_err();
+
+        assert_eq!(err.line, 1);
+        assert_eq!(err.token, "missing.mtl");
+        assert!(matches!(err.kind, ObjErrorKind::Io(_)));
+    }
+
+    #[test]
+    fn reports_bad_float() {
+        let err = parse_str("v 0.0 1.0 2.0\nv 0.0 abc 1.0\n").unwrap_err();
+
+        assert_eq!(err.line, 2);
+        assert_eq!(err.token, "abc");
+        assert!(matches!(err.kind, ObjErrorKind::BadFloat));
+    }
+
+    #[test]
+    fn reports_missing_component() {
+        let err = parse_str("v 0.0 1.0\n").unwrap_err();
+
+        assert_eq!(err.line, 1);
+        assert!(matches!(err.kind, ObjErrorKind::MissingComponent));
+    }
+
+    #[test]
+    fn reports_out_of_range_index() {
+        let source = format!("{}f 1/1/1 2/2/1 4/3/1\n", TRIANGLE);
+        let err = parse_str(&source).unwrap_err();
+
+        assert_eq!(err.line, 8);
+        assert_eq!(err.token, "4");
+        assert!(matches!(err.kind, ObjErrorKind::IndexOutOfRange));
+    }
+
+    #[test]
+    fn reports_out_of_range_negative_index() {
+        let source = format!("{}f -4 -2 -1\n", TRIANGLE);
+        let err = parse_str(&source).unwrap_err();
+
+        assert_eq!(err.token, "-4");
+        assert!(matches!(err.kind, ObjErrorKind::IndexOutOfRange));
+    }
+
+    #[test]
+    fn reports_missing_normal_index() {
+        let source = format!("{}f 1// 2// 3//\n", TRIANGLE);
+        let err = parse_str(&source).unwrap_err();
+
+        assert_eq!(err.token, "1//");
+        assert!(matches!(err.kind, ObjErrorKind::MissingComponent));
+    }
+
+    #[test]
+    fn reports_bad_index() {
+        let source = format!("{}f 1/1/1 2/x/1 3/3/1\n", TRIANGLE);
+        let err = parse_str(&source).unwrap_err();
+
+        assert_eq!(err.token, "x");
+        assert!(matches!(err.kind, ObjErrorKind::BadIndex));
+    }
+
+    #[test]
+    fn reports_truncated_face() {
+        let source = format!("{}f 1/1/1 2/2/1\n", TRIANGLE);
+        let err = parse_str(&source).unwrap_err();
+
+        assert_eq!(err.line, 8);
+        assert!(matches!(err.kind, ObjErrorKind::MissingComponent));
+    }
+
+    #[test]
+    fn reports_unsupported_directive() {
+        let err = parse_str("# a comment\n\ncstype bezier\n").unwrap_err();
+
+        assert_eq!(err.line, 3);
+        assert_eq!(err.token, "cstype");
+        assert!(matches!(err.kind, ObjErrorKind::UnsupportedDirective));
+    }
+
+    #[test]
+    fn reports_missing_file() {
+        let err = load("./does/not/exist.obj").unwrap_err();
+
+        assert_eq!(err.file, "./does/not/exist.obj");
+        assert!(matches!(err.kind, ObjErrorKind::Io(_)));
+        assert!(err.to_string().starts_with("./does/not/exist.obj: "));
+    }
 }
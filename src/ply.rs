@@ -0,0 +1,528 @@
+//! PLY reader for ASCII and binary (either byte order) files.
+//!
+//! Positions come from the `vertex` element's `x`, `y`, `z`, normals from
+//! `nx`, `ny`, `nz` and UVs from `u`/`v`, `s`/`t` or `texture_u`/`texture_v`
+//! when present. Faces are read from the `face` element's `vertex_indices`
+//! (or `vertex_index`) list, any other elements and properties are skipped.
+
+use crate::import::{self, ShapeError, ShapeErrorKind};
+use crate::mesh::{Indices, Mesh, Vertex};
+use crate::model::Model;
+use crate::object::{self, LoadOptions};
+use std::convert::TryInto;
+use std::fs;
+
+const UV_NAMES: [(&str, &str); 3] = [("u", "v"), ("s", "t"), ("texture_u", "texture_v")];
+
+/// Line, token and kind of an error, the file is added by `parse_with`.
+type Failure = (usize, String, ShapeErrorKind);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Encoding {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar(String, Scalar),
+    List(String, Scalar, Scalar),
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Scalar> {
+        Some(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+}
+
+impl Element {
+    fn scalar(&self, name: &str) -> Option<usize> {
+        self.properties
+            .iter()
+            .position(|property| matches!(property, Property::Scalar(n, _) if n == name))
+    }
+}
+
+/// Reads the values after the header one at a time, all as `f64` which holds
+/// every PLY type exactly.
+struct Body<'a> {
+    encoding: Encoding,
+    bytes: &'a [u8],
+    position: usize,
+    /// Line of the current token for ASCII files.
+    line: usize,
+}
+
+impl<'a> Body<'a> {
+    fn read(&mut self, scalar: Scalar) -> Result<f64, Failure> {
+        if self.encoding == Encoding::Ascii {
+            return self.read_token();
+        }
+
+        let size = scalar.size();
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + size)
+            .ok_or_else(|| (0, String::new(), ShapeErrorKind::Truncated))?;
+        self.position += size;
+
+        macro_rules! number {
+            ($type:ty) => {{
+                let bytes = bytes.try_into().unwrap();
+                (if self.encoding == Encoding::LittleEndian {
+                    <$type>::from_le_bytes(bytes)
+                } else {
+                    <$type>::from_be_bytes(bytes)
+                }) as f64
+            }};
+        }
+
+        Ok(match scalar {
+            Scalar::I8 => number!(i8),
+            Scalar::U8 => number!(u8),
+            Scalar::I16 => number!(i16),
+            Scalar::U16 => number!(u16),
+            Scalar::I32 => number!(i32),
+            Scalar::U32 => number!(u32),
+            Scalar::F32 => number!(f32),
+            Scalar::F64 => number!(f64),
+        })
+    }
+
+    fn read_token(&mut self) -> Result<f64, Failure> {
+        while let Some(&byte) = self.bytes.get(self.position) {
+            if !byte.is_ascii_whitespace() {
+                break;
+            }
+            if byte == b'\n' {
+                self.line += 1;
+            }
+            self.position += 1;
+        }
+
+        let start = self.position;
+        while self
+            .bytes
+            .get(self.position)
+            .is_some_and(|byte| !byte.is_ascii_whitespace())
+        {
+            self.position += 1;
+        }
+
+        if start == self.position {
+            return Err((0, String::new(), ShapeErrorKind::Truncated));
+        }
+
+        let token = String::from_utf8_lossy(&self.bytes[start..self.position]);
+        token
+            .parse()
+            .map_err(|_| (self.line, token.into_owned(), ShapeErrorKind::BadNumber))
+    }
+}
+
+pub fn load(path: &str) -> Result<Model, ShapeError> {
+    load_with(path, &LoadOptions::default())
+}
+
+pub fn load_with(path: &str, options: &LoadOptions) -> Result<Model, ShapeError> {
+    let bytes = fs::read(path).map_err(|err| ShapeError {
+        file: path.to_string(),
+        line: 0,
+        token: String::new(),
+        kind: ShapeErrorKind::Io(err),
+    })?;
+
+    parse_with(&bytes, path, options)
+}
+
+pub fn parse_with(bytes: &[u8], file: &str, options: &LoadOptions) -> Result<Model, ShapeError> {
+    let error = |(line, token, kind): Failure| ShapeError {
+        file: file.to_string(),
+        line,
+        token,
+        kind,
+    };
+
+    let (encoding, elements, header_size, header_lines) = parse_header(bytes).map_err(error)?;
+    let mut body = Body {
+        encoding,
+        bytes: &bytes[header_size..],
+        position: 0,
+        line: header_lines + 1,
+    };
+
+    let mut vertices = vec![];
+    let mut has_normals = false;
+    let mut has_uvs = false;
+    let mut triangles = vec![];
+
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => {
+                let (read, normals, uvs) = read_vertices(element, &mut body).map_err(error)?;
+                vertices = read;
+                has_normals = normals;
+                has_uvs = uvs;
+            }
+            "face" => triangles = read_faces(element, &mut body, &vertices).map_err(error)?,
+            _ => skip(element, &mut body).map_err(error)?,
+        }
+    }
+
+    let mut mesh = Mesh {
+        indices: Indices::new(triangles, vertices.len()),
+        vertices,
+    };
+    import::complete(&mut mesh, has_normals, has_uvs, options);
+
+    Ok(Model::from_mesh(&import::file_stem(file), mesh, None))
+}
+
+/// Returns the encoding, the elements, the header's size in bytes and how
+/// many lines it has.
+fn parse_header(bytes: &[u8]) -> Result<(Encoding, Vec<Element>, usize, usize), Failure> {
+    let mut encoding = None;
+    let mut elements: Vec<Element> = vec![];
+    let mut position = 0;
+
+    for number in 1.. {
+        let end = bytes[position..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .ok_or_else(|| (number, String::new(), ShapeErrorKind::Truncated))?;
+        let line = String::from_utf8_lossy(&bytes[position..position + end]);
+        position += end + 1;
+
+        let bad = |token: &str| (number, token.to_string(), ShapeErrorKind::BadHeader);
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+
+        match tokens.as_slice() {
+            ["ply"] if number == 1 => {}
+            _ if number == 1 => return Err(bad(line.trim())),
+            ["format", name, "1.0"] => {
+                encoding = Some(match *name {
+                    "ascii" => Encoding::Ascii,
+                    "binary_little_endian" => Encoding::LittleEndian,
+                    "binary_big_endian" => Encoding::BigEndian,
+                    _ => return Err((number, name.to_string(), ShapeErrorKind::UnsupportedFormat)),
+                });
+            }
+            ["comment", ..] | ["obj_info", ..] => {}
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| bad(count))?,
+                properties: vec![],
+            }),
+            ["property", "list", count, item, name] => {
+                let element = elements.last_mut().ok_or_else(|| bad(name))?;
+                let count = Scalar::parse(count).ok_or_else(|| bad(count))?;
+                let item = Scalar::parse(item).ok_or_else(|| bad(item))?;
+                element
+                    .properties
+                    .push(Property::List(name.to_string(), count, item));
+            }
+            ["property", scalar, name] => {
+                let element = elements.last_mut().ok_or_else(|| bad(name))?;
+                let scalar = Scalar::parse(scalar).ok_or_else(|| bad(scalar))?;
+                element
+                    .properties
+                    .push(Property::Scalar(name.to_string(), scalar));
+            }
+            ["end_header"] => {
+                let encoding = encoding.ok_or_else(|| bad("end_header"))?;
+                return Ok((encoding, elements, position, number));
+            }
+            _ => return Err(bad(line.trim())),
+        }
+    }
+
+    unreachable!()
+}
+
+fn read_vertices(element: &Element, body: &mut Body) -> Result<(Vec<Vertex>, bool, bool), Failure> {
+    let missing = |name: &str| (0, name.to_string(), ShapeErrorKind::MissingProperty);
+    let position = [
+        element.scalar("x").ok_or_else(|| missing("x"))?,
+        element.scalar("y").ok_or_else(|| missing("y"))?,
+        element.scalar("z").ok_or_else(|| missing("z"))?,
+    ];
+    let normal = match (
+        element.scalar("nx"),
+        element.scalar("ny"),
+        element.scalar("nz"),
+    ) {
+        (Some(x), Some(y), Some(z)) => Some([x, y, z]),
+        _ => None,
+    };
+    let uv = UV_NAMES
+        .iter()
+        .find_map(|(u, v)| Some([element.scalar(u)?, element.scalar(v)?]));
+
+    // every vertex takes at least a byte, so a count from a corrupt header
+    // can't reserve more than the file could hold
+    let remaining = body.bytes.len() - body.position;
+    let mut vertices = Vec::with_capacity(element.count.min(remaining));
+    let mut values = vec![0.0; element.properties.len()];
+
+    for _ in 0..element.count {
+        for (value, property) in values.iter_mut().zip(&element.properties) {
+            *value = match property {
+                Property::Scalar(_, scalar) => body.read(*scalar)?,
+                list => {
+                    skip_property(list, body)?;
+                    0.0
+                }
+            } as f32;
+        }
+
+        let vec3 = |[x, y, z]: [usize; 3]| glm::vec3(values[x], values[y], values[z]);
+        vertices.push(Vertex {
+            position: vec3(position),
+            uv: uv.map_or(glm::vec2(0.0, 0.0), |[u, v]| {
+                glm::vec2(values[u], values[v])
+            }),
+            normal: normal.map_or(glm::vec3(0.0, 0.0, 0.0), vec3),
+            tangent: glm::vec4(0.0, 0.0, 0.0, 0.0),
+        });
+    }
+
+    Ok((vertices, normal.is_some(), uv.is_some()))
+}
+
+/// Triangle indices of the faces, larger polygons are triangulated like the
+/// OBJ loader does.
+fn read_faces(
+    element: &Element,
+    body: &mut Body,
+    vertices: &[Vertex],
+) -> Result<Vec<u32>, Failure> {
+    let list = element
+        .properties
+        .iter()
+        .position(|property| {
+            matches!(property, Property::List(name, _, _)
+                if name == "vertex_indices" || name == "vertex_index")
+        })
+        .ok_or_else(|| {
+            (
+                0,
+                "vertex_indices".to_string(),
+                ShapeErrorKind::MissingProperty,
+            )
+        })?;
+
+    let mut triangles = vec![];
+    let mut face = vec![];
+
+    for _ in 0..element.count {
+        for (i, property) in element.properties.iter().enumerate() {
+            match property {
+                Property::List(_, count, item) if i == list => {
+                    face.clear();
+                    for _ in 0..body.read(*count)? as usize {
+                        let line = body.line;
+                        let index = body.read(*item)?;
+                        if index < 0.0 || index as usize >= vertices.len() {
+                            return Err((line, index.to_string(), ShapeErrorKind::IndexOutOfRange));
+                        }
+                        face.push(index as usize);
+                    }
+                }
+                _ => skip_property(property, body)?,
+            }
+        }
+
+        if face.len() < 3 {
+            continue;
+        }
+
+        let polygon: Vec<_> = face.iter().map(|&i| vertices[i].position).collect();
+        for triangle in object::triangulate(&polygon) {
+            triangles.extend(triangle.iter().map(|&corner| face[corner] as u32));
+        }
+    }
+
+    Ok(triangles)
+}
+
+fn skip(element: &Element, body: &mut Body) -> Result<(), Failure> {
+    for _ in 0..element.count {
+        for property in &element.properties {
+            skip_property(property, body)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn skip_property(property: &Property, body: &mut Body) -> Result<(), Failure> {
+    match property {
+        Property::Scalar(_, scalar) => {
+            body.read(*scalar)?;
+        }
+        Property::List(_, count, item) => {
+            for _ in 0..body.read(*count)? as usize {
+                body.read(*item)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(bytes: &[u8]) -> Result<Model, ShapeError> {
+        parse_with(bytes, "shapes/square.ply", &LoadOptions::default())
+    }
+
+    #[test]
+    fn reads_ascii() {
+        let source = "\
+ply
+format ascii 1.0
+comment a unit square, facing +Z
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property float s
+property float t
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 0 0 1 0 0
+1 0 0 0 0 1 1 0
+1 1 0 0 0 1 1 1
+0 1 0 0 0 1 0 1
+4 0 1 2 3
+";
+        let model = parse(source.as_bytes()).unwrap();
+        let mesh = &model.meshes[0].mesh;
+
+        assert_eq!(model.meshes[0].name, "square");
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.triangle_count(), 2);
+        let corner = mesh
+            .vertices
+            .iter()
+            .find(|vertex| vertex.position == glm::vec3(1.0, 1.0, 0.0))
+            .unwrap();
+        assert_eq!(corner.uv, glm::vec2(1.0, 1.0));
+        assert_eq!(corner.normal, glm::vec3(0.0, 0.0, 1.0));
+        assert_eq!(corner.tangent, glm::vec4(1.0, 0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn reads_big_endian_and_skips_unknown_data() {
+        let header = "\
+ply
+format binary_big_endian 1.0
+element vertex 3
+property double x
+property double y
+property double z
+property uchar red
+element face 1
+property uchar flags
+property list uchar uint vertex_indices
+element camera 1
+property float view_px
+end_header
+";
+        let mut bytes = header.as_bytes().to_vec();
+        for position in &[[0.0f64, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]] {
+            for coordinate in position {
+                bytes.extend(&coordinate.to_be_bytes());
+            }
+            bytes.push(255);
+        }
+        bytes.extend(&[7, 3]);
+        for index in &[0u32, 1, 2] {
+            bytes.extend(&index.to_be_bytes());
+        }
+        bytes.extend(&1.5f32.to_be_bytes());
+
+        let mesh = parse(&bytes).unwrap().meshes.remove(0).mesh;
+
+        assert_eq!(mesh.triangle_count(), 1);
+        assert_eq!(mesh.vertices[1].position, glm::vec3(1.0, 0.0, 0.0));
+        // generated from the face and the spherical projection
+        assert!(glm::length(mesh.vertices[0].normal - glm::vec3(0.0, 0.0, 1.0)) < 1e-6);
+        assert!(mesh.vertices.iter().all(|vertex| vertex.tangent.w != 0.0));
+    }
+
+    #[test]
+    fn reports_errors() {
+        let header = "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nproperty float y\nproperty float z\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n";
+
+        let err = parse(format!("{}0 0 0\n3 0 1 2\n", header).as_bytes()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "shapes/square.ply:11: index out of range '1'"
+        );
+
+        let err = parse(format!("{}0 zero 0\n", header).as_bytes()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "shapes/square.ply:10: invalid number 'zero'"
+        );
+
+        let err = parse(format!("{}0 0 0\n3 0", header).as_bytes()).unwrap_err();
+        assert!(matches!(err.kind, ShapeErrorKind::Truncated));
+
+        let huge = header.replace("vertex 1", "vertex 999999999999999999");
+        let err = parse(format!("{}0 0 0\n", huge).as_bytes()).unwrap_err();
+        assert!(matches!(err.kind, ShapeErrorKind::Truncated));
+
+        let err = parse(b"ply\nformat binary_middle_endian 1.0\nend_header\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "shapes/square.ply:2: unsupported format 'binary_middle_endian'"
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! How a texture is sampled: wrapping, filtering, mipmaps and anisotropy,
+//! given with the texture when it is loaded.
+
+use crate::extensions;
+use gl::types::{GLenum, GLint};
+
+// from ARB/EXT_texture_filter_anisotropic, same values as in GL 4.6
+const TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FF;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Wrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    /// Reads outside the texture get `SamplerDesc::border_color`.
+    ClampToBorder,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+/// Whether mipmaps are built, and how the two nearest levels are used.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MipmapMode {
+    None,
+    Nearest,
+    Linear,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SamplerDesc {
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    pub mipmaps: MipmapMode,
+    /// Maximum anisotropy, 1 turns it off. Clamped to what the driver
+    /// supports and ignored where it isn't supported at all.
+    pub anisotropy: f32,
+    pub border_color: [f32; 4],
+}
+
+impl Wrap {
+    fn gl_enum(self) -> GLenum {
+        match self {
+            Wrap::Repeat => gl::REPEAT,
+            Wrap::MirroredRepeat => gl::MIRRORED_REPEAT,
+            Wrap::ClampToEdge => gl::CLAMP_TO_EDGE,
+            Wrap::ClampToBorder => gl::CLAMP_TO_BORDER,
+        }
+    }
+}
+
+impl Filter {
+    fn gl_enum(self) -> GLenum {
+        match self {
+            Filter::Nearest => gl::NEAREST,
+            Filter::Linear => gl::LINEAR,
+        }
+    }
+}
+
+impl Default for SamplerDesc {
+    /// Repeating and trilinear, like GL's defaults with mipmaps.
+    fn default() -> SamplerDesc {
+        SamplerDesc {
+            wrap_s: Wrap::Repeat,
+            wrap_t: Wrap::Repeat,
+            min_filter: Filter::Linear,
+            mag_filter: Filter::Linear,
+            mipmaps: MipmapMode::Linear,
+            anisotropy: 1.0,
+            border_color: [0.0; 4],
+        }
+    }
+}
+
+impl SamplerDesc {
+    /// For equirectangular planet maps: wraps around the globe horizontally
+    /// but not over the poles, and stays sharp towards the limb.
+    pub fn planet() -> SamplerDesc {
+        SamplerDesc {
+            wrap_t: Wrap::ClampToEdge,
+            anisotropy: 8.0,
+            ..SamplerDesc::default()
+        }
+    }
+
+    /// For skies and other cubemaps seen from inside: clamped, so that faces
+    /// meet without seams, and without mipmaps, which would dim single-texel
+    /// stars.
+    pub fn skybox() -> SamplerDesc {
+        SamplerDesc {
+            wrap_s: Wrap::ClampToEdge,
+            wrap_t: Wrap::ClampToEdge,
+            mipmaps: MipmapMode::None,
+            ..SamplerDesc::default()
+        }
+    }
+
+    /// Nearest and unfiltered, for lookup tables and other data.
+    pub fn nearest() -> SamplerDesc {
+        SamplerDesc {
+            wrap_s: Wrap::ClampToEdge,
+            wrap_t: Wrap::ClampToEdge,
+            min_filter: Filter::Nearest,
+            mag_filter: Filter::Nearest,
+            mipmaps: MipmapMode::None,
+            ..SamplerDesc::default()
+        }
+    }
+
+    pub fn has_mipmaps(&self) -> bool {
+        self.mipmaps != MipmapMode::None
+    }
+
+    /// `TEXTURE_MIN_FILTER`, which also carries the mipmap mode.
+    fn gl_min_filter(&self) -> GLenum {
+        match (self.min_filter, self.mipmaps) {
+            (filter, MipmapMode::None) => filter.gl_enum(),
+            (Filter::Nearest, MipmapMode::Nearest) => gl::NEAREST_MIPMAP_NEAREST,
+            (Filter::Nearest, MipmapMode::Linear) => gl::NEAREST_MIPMAP_LINEAR,
+            (Filter::Linear, MipmapMode::Nearest) => gl::LINEAR_MIPMAP_NEAREST,
+            (Filter::Linear, MipmapMode::Linear) => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+
+    /// Sets the parameters of the texture bound to `target`.
+    pub fn apply(&self, target: GLenum) {
+        unsafe {
+            gl::TexParameteri(target, gl::TEXTURE_WRAP_S, self.wrap_s.gl_enum() as GLint);
+            gl::TexParameteri(target, gl::TEXTURE_WRAP_T, self.wrap_t.gl_enum() as GLint);
+            if target == gl::TEXTURE_CUBE_MAP {
+                gl::TexParameteri(target, gl::TEXTURE_WRAP_R, self.wrap_t.gl_enum() as GLint);
+            }
+            gl::TexParameteri(
+                target,
+                gl::TEXTURE_MIN_FILTER,
+                self.gl_min_filter() as GLint,
+            );
+            gl::TexParameteri(
+                target,
+                gl::TEXTURE_MAG_FILTER,
+                self.mag_filter.gl_enum() as GLint,
+            );
+            gl::TexParameterfv(target, gl::TEXTURE_BORDER_COLOR, self.border_color.as_ptr());
+
+            if self.anisotropy > 1.0
+                && extensions::has_any(&[
+                    "GL_ARB_texture_filter_anisotropic",
+                    "GL_EXT_texture_filter_anisotropic",
+                ])
+            {
+                let mut max = 1.0;
+                gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max);
+                gl::TexParameterf(target, TEXTURE_MAX_ANISOTROPY, self.anisotropy.min(max));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combines_min_filter_and_mipmaps() {
+        assert_eq!(
+            SamplerDesc::default().gl_min_filter(),
+            gl::LINEAR_MIPMAP_LINEAR
+        );
+        assert_eq!(SamplerDesc::nearest().gl_min_filter(), gl::NEAREST);
+
+        let blocky = SamplerDesc {
+            min_filter: Filter::Nearest,
+            mipmaps: MipmapMode::Nearest,
+            ..SamplerDesc::default()
+        };
+        assert_eq!(blocky.gl_min_filter(), gl::NEAREST_MIPMAP_NEAREST);
+        assert!(blocky.has_mipmaps());
+        assert!(!SamplerDesc::nearest().has_mipmaps());
+    }
+}
@@ -0,0 +1,112 @@
+//! The background: a cubemap drawn around the camera at infinite depth,
+//! behind everything else.
+
+use crate::texture::Texture;
+use gl::types::{GLchar, GLint, GLuint};
+
+/// Corners of the two triangles of each face of a cube around the origin.
+#[rustfmt::skip]
+const CUBE: [f32; 108] = [
+    -1.0,  1.0, -1.0,  -1.0, -1.0, -1.0,   1.0, -1.0, -1.0,
+     1.0, -1.0, -1.0,   1.0,  1.0, -1.0,  -1.0,  1.0, -1.0,
+    -1.0, -1.0,  1.0,  -1.0, -1.0, -1.0,  -1.0,  1.0, -1.0,
+    -1.0,  1.0, -1.0,  -1.0,  1.0,  1.0,  -1.0, -1.0,  1.0,
+     1.0, -1.0, -1.0,   1.0, -1.0,  1.0,   1.0,  1.0,  1.0,
+     1.0,  1.0,  1.0,   1.0,  1.0, -1.0,   1.0, -1.0, -1.0,
+    -1.0, -1.0,  1.0,  -1.0,  1.0,  1.0,   1.0,  1.0,  1.0,
+     1.0,  1.0,  1.0,   1.0, -1.0,  1.0,  -1.0, -1.0,  1.0,
+    -1.0,  1.0, -1.0,   1.0,  1.0, -1.0,   1.0,  1.0,  1.0,
+     1.0,  1.0,  1.0,  -1.0,  1.0,  1.0,  -1.0,  1.0, -1.0,
+    -1.0, -1.0, -1.0,  -1.0, -1.0,  1.0,   1.0, -1.0, -1.0,
+     1.0, -1.0, -1.0,  -1.0, -1.0,  1.0,   1.0, -1.0,  1.0,
+];
+
+/// A cubemap and the cube it is drawn on. The vertex buffer is deleted when
+/// dropped, so, like `Texture`, it must not outlive its context.
+pub struct Skybox {
+    pub cubemap: Texture,
+    program: GLuint,
+    vertex_buffer: GLuint,
+    view_rotation_projection: GLint,
+    sky: GLint,
+}
+
+impl Skybox {
+    /// Draws `cubemap` with `program`, the skybox shaders from
+    /// `resources/shaders`.
+    pub fn new(cubemap: Texture, program: GLuint) -> Skybox {
+        let mut vertex_buffer = 0;
+
+        unsafe {
+            gl::GenBuffers(1, &mut vertex_buffer);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vertex_buffer);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                std::mem::size_of_val(&CUBE) as isize,
+                CUBE.as_ptr() as *const std::ffi::c_void,
+                gl::STATIC_DRAW,
+            );
+        }
+
+        let location =
+            |name: &str| unsafe { gl::GetUniformLocation(program, name.as_ptr() as *const GLchar) };
+
+        Skybox {
+            cubemap,
+            program,
+            vertex_buffer,
+            view_rotation_projection: location("ViewRotationProjection\0"),
+            sky: location("sky\0"),
+        }
+    }
+
+    /// Draws behind whatever is already in the depth buffer, best done after
+    /// everything else so hidden sky is never shaded. Uses texture unit 0 and
+    /// leaves `program` in use.
+    pub fn draw(&self, view: &glm::Matrix4<f32>, projection: &glm::Matrix4<f32>) {
+        let rotation = glm::mat4(
+            view[0][0], view[0][1], view[0][2], 0.0, view[1][0], view[1][1], view[1][2], 0.0,
+            view[2][0], view[2][1], view[2][2], 0.0, 0.0, 0.0, 0.0, 1.0,
+        );
+        let view_rotation_projection = *projection * rotation;
+
+        unsafe {
+            gl::UseProgram(self.program);
+            gl::UniformMatrix4fv(
+                self.view_rotation_projection,
+                1,
+                gl::FALSE,
+                &view_rotation_projection[0][0],
+            );
+            gl::Uniform1i(self.sky, 0);
+
+            // the far plane itself has to pass, and the sky never hides anything
+            gl::DepthFunc(gl::LEQUAL);
+            gl::DepthMask(gl::FALSE);
+        }
+
+        self.cubemap.bind_to_unit(0);
+
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vertex_buffer);
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, std::ptr::null());
+            for attribute in 1..4 {
+                gl::DisableVertexAttribArray(attribute);
+            }
+
+            gl::DrawArrays(gl::TRIANGLES, 0, (CUBE.len() / 3) as i32);
+
+            gl::DepthMask(gl::TRUE);
+            gl::DepthFunc(gl::LESS);
+        }
+    }
+}
+
+impl Drop for Skybox {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.vertex_buffer);
+        }
+    }
+}
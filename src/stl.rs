@@ -0,0 +1,241 @@
+//! STL reader for binary and ASCII files. STL is a bare triangle soup, so
+//! corners are welded by position and everything else is generated.
+
+use crate::import::{self, ShapeError, ShapeErrorKind};
+use crate::model::Model;
+use crate::object::LoadOptions;
+use std::convert::TryInto;
+use std::fs;
+
+const HEADER_SIZE: usize = 84;
+const TRIANGLE_SIZE: usize = 50;
+
+pub fn load(path: &str) -> Result<Model, ShapeError> {
+    load_with(path, &LoadOptions::default())
+}
+
+pub fn load_with(path: &str, options: &LoadOptions) -> Result<Model, ShapeError> {
+    let bytes = fs::read(path).map_err(|err| ShapeError {
+        file: path.to_string(),
+        line: 0,
+        token: String::new(),
+        kind: ShapeErrorKind::Io(err),
+    })?;
+
+    parse_with(&bytes, path, options)
+}
+
+pub fn parse_with(bytes: &[u8], file: &str, options: &LoadOptions) -> Result<Model, ShapeError> {
+    let (name, positions) = if is_binary(bytes) {
+        (None, parse_binary(bytes))
+    } else {
+        parse_ascii(bytes, file)?
+    };
+
+    let mut mesh = import::weld(&positions);
+    import::complete(&mut mesh, false, false, options);
+
+    let name = name.unwrap_or_else(|| import::file_stem(file));
+    Ok(Model::from_mesh(&name, mesh, None))
+}
+
+/// Whether `bytes` are laid out as binary STL: a header, a triangle count
+/// and that many triangles. Some exporters pad the end, so trailing bytes
+/// are allowed unless the header starts like ASCII STL, which is never the
+/// exact size.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    if bytes.len() < HEADER_SIZE {
+        return false;
+    }
+
+    let size = match triangle_count(bytes)
+        .checked_mul(TRIANGLE_SIZE)
+        .and_then(|size| size.checked_add(HEADER_SIZE))
+    {
+        Some(size) => size,
+        None => return false,
+    };
+    if bytes.starts_with(b"solid") {
+        bytes.len() == size
+    } else {
+        bytes.len() >= size
+    }
+}
+
+fn triangle_count(bytes: &[u8]) -> usize {
+    u32::from_le_bytes(bytes[80..84].try_into().unwrap()) as usize
+}
+
+fn parse_binary(bytes: &[u8]) -> Vec<glm::Vector3<f32>> {
+    let float = |at: usize| f32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+
+    bytes[HEADER_SIZE..]
+        .chunks_exact(TRIANGLE_SIZE)
+        .take(triangle_count(bytes))
+        .enumerate()
+        .flat_map(|(i, _)| {
+            // each triangle is a facet normal, three corners and two
+            // attribute bytes, the normal is regenerated anyway
+            let start = HEADER_SIZE + i * TRIANGLE_SIZE + 12;
+            (0..3).map(move |corner| start + corner * 12)
+        })
+        .map(|at| glm::vec3(float(at), float(at + 4), float(at + 8)))
+        .collect()
+}
+
+fn parse_ascii(
+    bytes: &[u8],
+    file: &str,
+) -> Result<(Option<String>, Vec<glm::Vector3<f32>>), ShapeError> {
+    let error = |line, token: &str, kind| ShapeError {
+        file: file.to_string(),
+        line,
+        token: token.to_string(),
+        kind,
+    };
+
+    let text = String::from_utf8_lossy(bytes);
+    let mut name = None;
+    let mut positions = vec![];
+
+    for (number, line) in text.lines().enumerate() {
+        let number = number + 1;
+        let mut tokens = line.split_whitespace();
+
+        match tokens.next() {
+            Some("solid") => {
+                let rest: Vec<_> = tokens.collect();
+                if !rest.is_empty() {
+                    name = Some(rest.join(" "));
+                }
+            }
+            Some("vertex") => {
+                let mut coordinate = || {
+                    let token = tokens
+                        .next()
+                        .ok_or_else(|| error(number, line.trim(), ShapeErrorKind::Truncated))?;
+                    token
+                        .parse::<f32>()
+                        .map_err(|_| error(number, token, ShapeErrorKind::BadNumber))
+                };
+                positions.push(glm::vec3(coordinate()?, coordinate()?, coordinate()?));
+            }
+            Some("facet") | Some("outer") | Some("endloop") | Some("endfacet")
+            | Some("endsolid") | None => {}
+            Some(token) => return Err(error(number, token, ShapeErrorKind::BadHeader)),
+        }
+    }
+
+    if positions.is_empty() && !text.trim_start().starts_with("solid") {
+        return Err(error(0, "", ShapeErrorKind::UnsupportedFormat));
+    }
+    if positions.len() % 3 != 0 {
+        return Err(error(0, "", ShapeErrorKind::Truncated));
+    }
+
+    Ok((name, positions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TETRAHEDRON: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0],
+        [0.0, 0.0, 1.0],
+    ];
+    const FACES: [[usize; 3]; 4] = [[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]];
+
+    fn ascii() -> String {
+        let mut text = "solid tetrahedron\n".to_string();
+        for face in &FACES {
+            text += "  facet normal 0 0 0\n    outer loop\n";
+            for &corner in face {
+                let [x, y, z] = TETRAHEDRON[corner];
+                text += &format!("      vertex {} {} {}\n", x, y, z);
+            }
+            text += "    endloop\n  endfacet\n";
+        }
+        text + "endsolid tetrahedron\n"
+    }
+
+    fn binary() -> Vec<u8> {
+        let mut bytes = b"solid but actually binary".to_vec();
+        bytes.resize(80, 0);
+        bytes.extend(&(FACES.len() as u32).to_le_bytes());
+        for face in &FACES {
+            bytes.extend(&[0; 12]);
+            for &corner in face {
+                for coordinate in &TETRAHEDRON[corner] {
+                    bytes.extend(&coordinate.to_le_bytes());
+                }
+            }
+            bytes.extend(&[0; 2]);
+        }
+        bytes
+    }
+
+    fn parse(bytes: &[u8]) -> Result<Model, ShapeError> {
+        parse_with(bytes, "shapes/tetrahedron.stl", &LoadOptions::default())
+    }
+
+    #[test]
+    fn reads_ascii_and_binary_alike() {
+        let from_ascii = parse(ascii().as_bytes()).unwrap();
+        let from_binary = parse(&binary()).unwrap();
+
+        assert_eq!(from_ascii.meshes[0].name, "tetrahedron");
+        assert_eq!(from_binary.meshes[0].name, "tetrahedron");
+        assert_eq!(from_ascii.meshes[0].mesh, from_binary.meshes[0].mesh);
+
+        let mesh = &from_binary.meshes[0].mesh;
+        assert_eq!(mesh.triangle_count(), 4);
+        // every face is at more than the crease angle to its neighbours
+        for triangle in 0..4 {
+            let corners: Vec<_> = (0..3)
+                .map(|i| mesh.vertices[mesh.indices.get(triangle * 3 + i)])
+                .collect();
+            let edge = glm::cross(
+                corners[1].position - corners[0].position,
+                corners[2].position - corners[0].position,
+            );
+            for corner in corners {
+                assert!(glm::dot(glm::normalize(edge), corner.normal) > 0.999);
+                assert!((0.0..=1.0).contains(&corner.uv.y));
+                assert_ne!(corner.tangent.w, 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn ignores_bytes_after_binary_triangles() {
+        let mut bytes = binary();
+        bytes[..5].copy_from_slice(b"exprt");
+        bytes.extend(&[0xAB; TRIANGLE_SIZE + 3]);
+
+        assert!(is_binary(&bytes));
+        assert_eq!(
+            parse(&bytes).unwrap().meshes[0].mesh,
+            parse(&binary()).unwrap().meshes[0].mesh
+        );
+
+        // a header like ASCII STL has to fit exactly
+        let mut bytes = binary();
+        bytes.push(b'\n');
+        assert!(!is_binary(&bytes));
+    }
+
+    #[test]
+    fn reports_bad_ascii() {
+        let err = parse(b"solid x\nfacet normal 0 0 1\nouter loop\nvertex 0 one 0\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "shapes/tetrahedron.stl:4: invalid number 'one'"
+        );
+
+        let err = parse(b"solid x\nvertex 0 0 0\nvertex 1 0 0\nendsolid\n").unwrap_err();
+        assert!(matches!(err.kind, ShapeErrorKind::Truncated));
+    }
+}
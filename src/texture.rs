@@ -1,44 +1,758 @@
-use gl::types::GLuint;
+use crate::compressed::{self, BlockFormat};
+use crate::cubemap;
+use crate::dds;
+use crate::ktx2;
+use crate::sampler::SamplerDesc;
+use crate::texture_bake;
+use gl::types::{GLenum, GLuint};
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
 
-pub fn load(texture: GLuint, path: &str) {
-    unsafe {
-        gl::BindTexture(gl::TEXTURE_2D, texture);
+#[derive(Debug)]
+pub enum TextureErrorKind {
+    Decode(String),
+    /// Cubemap faces that are not square or not all alike.
+    MismatchedFaces,
+}
+
+#[derive(Debug)]
+pub struct TextureError {
+    pub file: String,
+    pub kind: TextureErrorKind,
+}
+
+impl fmt::Display for TextureErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureErrorKind::Decode(message) => write!(f, "{}", message),
+            TextureErrorKind::MismatchedFaces => write!(
+                f,
+                "cubemap faces differ in size or format, or are not square"
+            ),
+        }
+    }
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.file, self.kind)
+    }
+}
+
+impl Error for TextureError {}
+
+/// A GL texture object, deleted when dropped, so it must not outlive the
+/// context it was created in.
+#[derive(Debug)]
+pub struct Texture {
+    id: GLuint,
+    /// `TEXTURE_2D` or `TEXTURE_CUBE_MAP`.
+    pub target: GLenum,
+    /// Of each face, for cubemaps.
+    pub width: u32,
+    pub height: u32,
+    /// Channels in the source image, 1 to 4.
+    pub channels: u32,
+    /// Internal format the image was uploaded as.
+    pub format: GLenum,
+    pub mipmapped: bool,
+}
+
+impl Texture {
+    /// Generates a new texture object and binds it to `target`.
+    fn generate(
+        target: GLenum,
+        width: u32,
+        height: u32,
+        channels: u32,
+        format: GLenum,
+        mipmapped: bool,
+    ) -> Texture {
+        let mut id = 0;
+
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(target, id);
+        }
+
+        Texture {
+            id,
+            target,
+            width,
+            height,
+            channels,
+            format,
+            mipmapped,
+        }
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    /// Binds to its target on the active texture unit.
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindTexture(self.target, self.id);
+        }
     }
 
-    let data = stb_image::image::load(path);
+    /// Binds to its target on texture unit `unit`, which is left active.
+    pub fn bind_to_unit(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+        }
+        self.bind();
+    }
+
+    /// Estimated video memory used, a full mipmap chain adds a third.
+    pub fn size_in_bytes(&self) -> usize {
+        let faces = if self.target == gl::TEXTURE_CUBE_MAP {
+            6
+        } else {
+            1
+        };
+        let base =
+            faces * self.width as usize * self.height as usize * bits_per_texel(self.format) / 8;
+
+        if self.mipmapped {
+            base * 4 / 3
+        } else {
+            base
+        }
+    }
+}
+
+fn bits_per_texel(format: GLenum) -> usize {
+    match format {
+        gl::R8 => 8,
+        gl::RG8 | gl::R16F => 16,
+        gl::RGB8 | gl::SRGB8 => 24,
+        gl::RGB16F => 48,
+        gl::RG16F | gl::R32F => 32,
+        gl::RGBA16F | gl::RG32F => 64,
+        gl::RGB32F => 96,
+        gl::RGBA32F => 128,
+        _ => compressed::bits_per_texel(format).unwrap_or(32),
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+    }
+}
+
+/// Storage for float images: half floats cover HDR color ranges at half the
+/// memory, full floats are for data that needs the precision.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FloatPrecision {
+    Half,
+    Full,
+}
+
+/// What 8-bit texels hold. Colors (albedo, emissive, skies) are sRGB
+/// encoded, and stored so that sampling decodes them to linear light for the
+/// shaders to light. Data (normals, heights, masks) is sampled as stored.
+/// Float texels are always linear.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorSpace {
+    Srgb,
+    Linear,
+}
+
+/// How `load_with` stores and samples an image.
+#[derive(Clone, Copy, Debug)]
+pub struct LoadOptions {
+    pub float_precision: FloatPrecision,
+    pub sampler: SamplerDesc,
+    /// sRGB by default, data textures have to ask for `Linear`. Files that
+    /// record their encoding (baked `.tex`, KTX2, and DDS with a DX10
+    /// header) stay linear if they say so.
+    pub color_space: ColorSpace,
+}
+
+impl Default for LoadOptions {
+    fn default() -> LoadOptions {
+        LoadOptions {
+            float_precision: FloatPrecision::Half,
+            sampler: SamplerDesc::default(),
+            color_space: ColorSpace::Srgb,
+        }
+    }
+}
 
-    match data {
-        stb_image::image::LoadResult::Error(message) => panic!(
-            "Error loading image of path: {} Error message: {}",
-            path, message
+/// Internal format, pixel format and pixel type for an image with
+/// `channels` channels, of floats if `float` is set, and sRGB encoded if
+/// `srgb` is. Grey is stored as sRGB RGB and swizzled from red, grey-alpha
+/// has no sRGB format and is spread to RGBA before it gets here.
+fn formats(
+    channels: u32,
+    float: bool,
+    srgb: bool,
+    precision: FloatPrecision,
+) -> (GLenum, GLenum, GLenum) {
+    let (format, unorm, srgb8, half, full) = match channels {
+        1 => (gl::RED, gl::R8, gl::SRGB8, gl::R16F, gl::R32F),
+        2 => (gl::RG, gl::RG8, gl::RG8, gl::RG16F, gl::RG32F),
+        3 => (gl::RGB, gl::RGB8, gl::SRGB8, gl::RGB16F, gl::RGB32F),
+        _ => (
+            gl::RGBA,
+            gl::RGBA8,
+            gl::SRGB8_ALPHA8,
+            gl::RGBA16F,
+            gl::RGBA32F,
         ),
-        stb_image::image::LoadResult::ImageU8(image) => unsafe {
-            gl::TexImage2D(
-                gl::TEXTURE_2D,
-                0,
-                gl::RGB as i32,
-                image.width as i32,
-                image.height as i32,
-                0,
-                gl::RGB,
-                gl::UNSIGNED_BYTE,
-                image.data.as_ptr() as *const std::ffi::c_void,
-            );
-            gl::GenerateMipmap(gl::TEXTURE_2D);
-        },
-        stb_image::image::LoadResult::ImageF32(image) => unsafe {
-            gl::TexImage2D(
-                gl::TEXTURE_2D,
-                0,
-                gl::RGB as i32,
+    };
+
+    match (float, precision) {
+        (false, _) if srgb => (srgb8, format, gl::UNSIGNED_BYTE),
+        (false, _) => (unorm, format, gl::UNSIGNED_BYTE),
+        (true, FloatPrecision::Half) => (half, format, gl::FLOAT),
+        (true, FloatPrecision::Full) => (full, format, gl::FLOAT),
+    }
+}
+
+/// Largest `UNPACK_ALIGNMENT` that rows of `row_size` bytes are padded to,
+/// tightly packed rows of odd widths need less than GL's default of 4.
+fn unpack_alignment(row_size: usize) -> i32 {
+    [8, 4, 2]
+        .iter()
+        .cloned()
+        .find(|&alignment| row_size % alignment == 0)
+        .unwrap_or(1) as i32
+}
+
+/// Decoded pixels, rows tightly packed from the top, or blocks of them as
+/// read from DDS and KTX2 files.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pixels {
+    U8(Vec<u8>),
+    F32(Vec<f32>),
+    Compressed(BlockFormat, Vec<u8>),
+}
+
+/// An image decoded on the CPU, which can happen on any thread, waiting to
+/// be uploaded with `upload_image` on the one with the GL context.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub channels: u32,
+    pub pixels: Pixels,
+}
+
+impl Image {
+    /// The image with its blocks decoded into 8-bit pixels, or a copy of it
+    /// if it isn't compressed.
+    pub fn decompressed(&self) -> Image {
+        match &self.pixels {
+            Pixels::Compressed(format, data) => {
+                compressed::decompress(*format, self.width, self.height, data)
+            }
+            _ => self.clone(),
+        }
+    }
+}
+
+/// Everything uploaded for one texture: a single face, or six in GL's order
+/// for a cubemap, each a mip chain starting at the full size image. Like
+/// `Image`, it can be read on any thread.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextureImages {
+    pub faces: Vec<Vec<Image>>,
+    /// Whether 8-bit pixels are sRGB encoded rather than linear. Float
+    /// pixels are always linear.
+    pub srgb: bool,
+}
+
+impl TextureImages {
+    /// A flat texture of `image` alone, its mipmaps are left to GL.
+    pub fn single(image: Image) -> TextureImages {
+        TextureImages {
+            srgb: matches!(image.pixels, Pixels::U8(_)),
+            faces: vec![vec![image]],
+        }
+    }
+
+    pub fn is_cubemap(&self) -> bool {
+        self.faces.len() == 6
+    }
+}
+
+pub fn load(path: &str) -> Result<Texture, TextureError> {
+    load_with(path, &LoadOptions::default())
+}
+
+/// Loads an image with as many channels as it has: grey images are spread
+/// to RGB when sampled, and float images (e.g. `.hdr`) stay floats. Baked
+/// `.tex`, `.dds` and `.ktx2` files come with their own mipmaps, and may be
+/// cubemaps. Block-compressed ones stay compressed on the GPU if the driver
+/// supports their format, and are decompressed on the CPU if not.
+pub fn load_with(path: &str, options: &LoadOptions) -> Result<Texture, TextureError> {
+    let images = read(path)?;
+
+    upload_images(&images, options).map_err(|kind| TextureError {
+        file: path.to_string(),
+        kind,
+    })
+}
+
+/// Reads a baked texture, a DDS or KTX2 file, or decodes any other image,
+/// without touching GL.
+pub fn read(path: &str) -> Result<TextureImages, TextureError> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    let error = |message: String| TextureError {
+        file: path.to_string(),
+        kind: TextureErrorKind::Decode(message),
+    };
+
+    match extension.as_deref() {
+        Some("tex") => texture_bake::load(path).map_err(|err| error(err.to_string())),
+        Some("dds") => dds::load(path).map_err(|err| error(err.to_string())),
+        Some("ktx2") => ktx2::load(path).map_err(|err| error(err.to_string())),
+        _ => decode(path).map(TextureImages::single),
+    }
+}
+
+/// Reads and decodes the image at `path` without touching GL.
+pub fn decode(path: &str) -> Result<Image, TextureError> {
+    match stb_image::image::load(path) {
+        stb_image::image::LoadResult::Error(message) => Err(TextureError {
+            file: path.to_string(),
+            kind: TextureErrorKind::Decode(message),
+        }),
+        stb_image::image::LoadResult::ImageU8(image) => Ok(Image {
+            width: image.width as u32,
+            height: image.height as u32,
+            channels: image.depth as u32,
+            pixels: Pixels::U8(image.data),
+        }),
+        stb_image::image::LoadResult::ImageF32(image) => Ok(Image {
+            width: image.width as u32,
+            height: image.height as u32,
+            channels: image.depth as u32,
+            pixels: Pixels::F32(image.data),
+        }),
+    }
+}
+
+pub fn upload_image(image: &Image, options: &LoadOptions) -> Texture {
+    upload(
+        gl::TEXTURE_2D,
+        &[std::slice::from_ref(image)],
+        true,
+        options,
+    )
+}
+
+/// Creates a flat texture or a cubemap, with the mipmaps that come with
+/// `images`, or generated ones if there are none and the sampler uses them.
+pub fn upload_images(
+    images: &TextureImages,
+    options: &LoadOptions,
+) -> Result<Texture, TextureErrorKind> {
+    let faces: Vec<&[Image]> = images.faces.iter().map(Vec::as_slice).collect();
+
+    if images.is_cubemap() {
+        check_cubemap(&faces)?;
+        Ok(upload(gl::TEXTURE_CUBE_MAP, &faces, images.srgb, options))
+    } else {
+        Ok(upload(gl::TEXTURE_2D, &faces[..1], images.srgb, options))
+    }
+}
+
+/// Loads a cubemap from six images, in GL's face order +X, -X, +Y, -Y, +Z,
+/// -Z. They have to be square and alike in size and channels.
+pub fn load_cubemap(paths: [&str; 6], options: &LoadOptions) -> Result<Texture, TextureError> {
+    let [px, nx, py, ny, pz, nz] = paths.map(decode);
+    let faces = [px?, nx?, py?, ny?, pz?, nz?];
+
+    upload_cubemap(&faces, options).map_err(|kind| TextureError {
+        file: paths[0].to_string(),
+        kind,
+    })
+}
+
+/// Loads a cubemap with `size` by `size` faces from an equirectangular
+/// panorama.
+pub fn load_equirect_cubemap(
+    path: &str,
+    size: u32,
+    options: &LoadOptions,
+) -> Result<Texture, TextureError> {
+    let faces = cubemap::from_equirect(&decode(path)?, size);
+
+    upload_cubemap(&faces, options).map_err(|kind| TextureError {
+        file: path.to_string(),
+        kind,
+    })
+}
+
+/// Creates a cubemap from faces in GL's order, see `cubemap`.
+pub fn upload_cubemap(
+    faces: &[Image; 6],
+    options: &LoadOptions,
+) -> Result<Texture, TextureErrorKind> {
+    let faces = [0, 1, 2, 3, 4, 5].map(|i| std::slice::from_ref(&faces[i]));
+
+    check_cubemap(&faces)?;
+    Ok(upload(gl::TEXTURE_CUBE_MAP, &faces, true, options))
+}
+
+/// Cubemap faces have to be square, and alike in size, channels, pixel type
+/// and mip levels.
+fn check_cubemap(faces: &[&[Image]]) -> Result<(), TextureErrorKind> {
+    let first = &faces[0];
+    let alike = faces.iter().all(|levels| {
+        levels.len() == first.len()
+            && levels.iter().zip(first.iter()).all(|(level, first)| {
+                level.width == first.width
+                    && level.height == first.height
+                    && level.channels == first.channels
+                    && level.pixels.is_float() == first.pixels.is_float()
+                    && level.pixels.block_format() == first.pixels.block_format()
+            })
+    });
+
+    if alike && !first.is_empty() && first[0].width == first[0].height {
+        Ok(())
+    } else {
+        Err(TextureErrorKind::MismatchedFaces)
+    }
+}
+
+impl Pixels {
+    pub fn is_float(&self) -> bool {
+        matches!(self, Pixels::F32(_))
+    }
+
+    pub fn block_format(&self) -> Option<BlockFormat> {
+        match self {
+            Pixels::Compressed(format, _) => Some(*format),
+            _ => None,
+        }
+    }
+
+    fn as_ptr(&self) -> *const std::ffi::c_void {
+        match self {
+            Pixels::U8(data) => data.as_ptr() as *const _,
+            Pixels::F32(data) => data.as_ptr() as *const _,
+            Pixels::Compressed(_, data) => data.as_ptr() as *const _,
+        }
+    }
+}
+
+/// A single pixel of `color`, for materials that come without an image.
+pub fn load_solid(color: [u8; 3]) -> Texture {
+    let image = Image {
+        width: 1,
+        height: 1,
+        channels: 3,
+        pixels: Pixels::U8(color.to_vec()),
+    };
+
+    upload_image(&image, &LoadOptions::default())
+}
+
+/// Creates a texture for `target` from the mip chain of each face, which
+/// have to be alike. A chain of just the full size image gets generated
+/// mipmaps if the sampler uses them, unless it is compressed. `srgb` is
+/// whether the images are sRGB encoded as far as their source knows, the
+/// options get the final say.
+fn upload(target: GLenum, faces: &[&[Image]], srgb: bool, options: &LoadOptions) -> Texture {
+    let first = &faces[0][0];
+    let srgb = stored_as_srgb(srgb, options.color_space, first.pixels.is_float());
+
+    let convert: Option<fn(&Image) -> Image> = match first.pixels.block_format() {
+        Some(format) if !format.is_supported(srgb) => Some(Image::decompressed),
+        None if srgb && first.channels == 2 => Some(grey_alpha_to_rgba),
+        _ => None,
+    };
+    match convert {
+        Some(convert) => {
+            let converted: Vec<Vec<Image>> = faces
+                .iter()
+                .map(|levels| levels.iter().map(convert).collect())
+                .collect();
+            let converted: Vec<&[Image]> = converted.iter().map(Vec::as_slice).collect();
+
+            create(target, &converted, first.channels, srgb, options)
+        }
+        None => create(target, faces, first.channels, srgb, options),
+    }
+}
+
+/// Whether a texture is stored as sRGB: only when its source says it is
+/// sRGB encoded and it was asked for as color, and never for floats.
+fn stored_as_srgb(source_srgb: bool, color_space: ColorSpace, float: bool) -> bool {
+    source_srgb && color_space == ColorSpace::Srgb && !float
+}
+
+/// The GL side of `upload`, once the images are in a form GL takes.
+/// `channels` is recorded as the source's, which they may have been
+/// widened from.
+fn create(
+    target: GLenum,
+    faces: &[&[Image]],
+    channels: u32,
+    srgb: bool,
+    options: &LoadOptions,
+) -> Texture {
+    let first = &faces[0][0];
+    let levels = faces[0].len();
+    let generate_mipmaps =
+        levels == 1 && options.sampler.has_mipmaps() && first.pixels.block_format().is_none();
+    let internal_format = internal_format(first, srgb, options);
+    let texture = Texture::generate(
+        target,
+        first.width,
+        first.height,
+        channels,
+        internal_format,
+        levels > 1 || generate_mipmaps,
+    );
+
+    for (i, levels) in faces.iter().enumerate() {
+        let face_target = if target == gl::TEXTURE_CUBE_MAP {
+            gl::TEXTURE_CUBE_MAP_POSITIVE_X + i as GLenum
+        } else {
+            target
+        };
+
+        for (level, image) in levels.iter().enumerate() {
+            tex_image(face_target, level, image, srgb, options);
+        }
+    }
+
+    finish(&texture, first.channels, options);
+    unsafe {
+        if generate_mipmaps {
+            gl::GenerateMipmap(target);
+        } else {
+            // a chain that stops short of 1x1 is still complete
+            gl::TexParameteri(target, gl::TEXTURE_MAX_LEVEL, levels as i32 - 1);
+        }
+    }
+
+    texture
+}
+
+fn internal_format(image: &Image, srgb: bool, options: &LoadOptions) -> GLenum {
+    match image.pixels.block_format() {
+        Some(format) => format.gl_internal_format(srgb),
+        None => {
+            formats(
+                image.channels,
+                image.pixels.is_float(),
+                srgb,
+                options.float_precision,
+            )
+            .0
+        }
+    }
+}
+
+/// A grey-alpha image as RGBA, for storing as sRGB.
+fn grey_alpha_to_rgba(image: &Image) -> Image {
+    let pixels = match &image.pixels {
+        Pixels::U8(data) => data
+            .chunks_exact(2)
+            .flat_map(|texel| [texel[0], texel[0], texel[0], texel[1]])
+            .collect(),
+        _ => unreachable!("only 8-bit images are sRGB"),
+    };
+
+    Image {
+        channels: 4,
+        pixels: Pixels::U8(pixels),
+        ..*image
+    }
+}
+
+/// Uploads `image` as mip level `level` of `target`, a 2D texture or a
+/// cubemap face.
+fn tex_image(target: GLenum, level: usize, image: &Image, srgb: bool, options: &LoadOptions) {
+    if let Pixels::Compressed(format, data) = &image.pixels {
+        unsafe {
+            gl::CompressedTexImage2D(
+                target,
+                level as i32,
+                format.gl_internal_format(srgb),
                 image.width as i32,
                 image.height as i32,
                 0,
-                gl::RGB,
-                gl::UNSIGNED_BYTE,
-                image.data.as_ptr() as *const std::ffi::c_void,
+                data.len() as i32,
+                data.as_ptr() as *const _,
             );
-            gl::GenerateMipmap(gl::TEXTURE_2D);
-        },
+        }
+        return;
+    }
+
+    let float = image.pixels.is_float();
+    let (internal_format, format, pixel_type) =
+        formats(image.channels, float, srgb, options.float_precision);
+    let component_size = if float { 4 } else { 1 };
+
+    unsafe {
+        gl::PixelStorei(
+            gl::UNPACK_ALIGNMENT,
+            unpack_alignment(image.width as usize * image.channels as usize * component_size),
+        );
+        gl::TexImage2D(
+            target,
+            level as i32,
+            internal_format as i32,
+            image.width as i32,
+            image.height as i32,
+            0,
+            format,
+            pixel_type,
+            image.pixels.as_ptr(),
+        );
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+    }
+}
+
+/// Sets up sampling of the bound `texture` once its images, of `channels`
+/// channels as stored, are uploaded.
+fn finish(texture: &Texture, channels: u32, options: &LoadOptions) {
+    unsafe {
+        // grey (and grey-alpha) images read as grey rather than red
+        let swizzle = match channels {
+            1 => Some([gl::RED, gl::RED, gl::RED, gl::ONE]),
+            2 => Some([gl::RED, gl::RED, gl::RED, gl::GREEN]),
+            _ => None,
+        };
+        if let Some(swizzle) = swizzle {
+            let swizzle = swizzle.map(|channel| channel as i32);
+            gl::TexParameteriv(texture.target, gl::TEXTURE_SWIZZLE_RGBA, swizzle.as_ptr());
+        }
+
+        options.sampler.apply(texture.target);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_unreadable_images() {
+        // fails while decoding, before any GL call
+        let err = load("resources/textures/missing.png").unwrap_err();
+
+        assert!(matches!(err.kind, TextureErrorKind::Decode(_)));
+        assert!(err
+            .to_string()
+            .starts_with("resources/textures/missing.png: "));
+    }
+
+    #[test]
+    fn picks_formats_by_channels() {
+        let half = FloatPrecision::Half;
+
+        assert_eq!(
+            formats(1, false, false, half),
+            (gl::R8, gl::RED, gl::UNSIGNED_BYTE)
+        );
+        assert_eq!(
+            formats(2, false, false, half),
+            (gl::RG8, gl::RG, gl::UNSIGNED_BYTE)
+        );
+        assert_eq!(
+            formats(4, false, false, half),
+            (gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE)
+        );
+        assert_eq!(
+            formats(3, true, false, half),
+            (gl::RGB16F, gl::RGB, gl::FLOAT)
+        );
+        assert_eq!(
+            formats(3, true, false, FloatPrecision::Full),
+            (gl::RGB32F, gl::RGB, gl::FLOAT)
+        );
+    }
+
+    #[test]
+    fn stores_colors_as_srgb() {
+        let half = FloatPrecision::Half;
+
+        assert_eq!(
+            formats(1, false, true, half),
+            (gl::SRGB8, gl::RED, gl::UNSIGNED_BYTE)
+        );
+        assert_eq!(
+            formats(4, false, true, half),
+            (gl::SRGB8_ALPHA8, gl::RGBA, gl::UNSIGNED_BYTE)
+        );
+        // floats are linear whatever they hold
+        assert_eq!(
+            formats(3, true, true, half),
+            (gl::RGB16F, gl::RGB, gl::FLOAT)
+        );
+        assert_eq!(
+            BlockFormat::Bc7.gl_internal_format(true),
+            gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM
+        );
+        assert_eq!(
+            BlockFormat::Bc5.gl_internal_format(true),
+            gl::COMPRESSED_RG_RGTC2
+        );
+
+        let grey_alpha = Image {
+            width: 2,
+            height: 1,
+            channels: 2,
+            pixels: Pixels::U8(vec![10, 20, 30, 40]),
+        };
+        assert_eq!(
+            grey_alpha_to_rgba(&grey_alpha).pixels,
+            Pixels::U8(vec![10, 10, 10, 20, 30, 30, 30, 40])
+        );
+    }
+
+    #[test]
+    fn lets_options_override_the_source_color_space() {
+        assert!(stored_as_srgb(true, ColorSpace::Srgb, false));
+        assert!(!stored_as_srgb(true, ColorSpace::Linear, false));
+        assert!(!stored_as_srgb(false, ColorSpace::Srgb, false));
+        assert!(!stored_as_srgb(true, ColorSpace::Srgb, true));
+
+        // a BC7 file flagged sRGB, loaded as data such as a normal map
+        let images = TextureImages {
+            faces: vec![vec![Image {
+                width: 4,
+                height: 4,
+                channels: 4,
+                pixels: Pixels::Compressed(BlockFormat::Bc7, vec![0; 16]),
+            }]],
+            srgb: true,
+        };
+        let options = LoadOptions {
+            color_space: ColorSpace::Linear,
+            ..LoadOptions::default()
+        };
+        let image = &images.faces[0][0];
+        let srgb = stored_as_srgb(images.srgb, options.color_space, image.pixels.is_float());
+        assert_eq!(
+            internal_format(image, srgb, &options),
+            gl::COMPRESSED_RGBA_BPTC_UNORM
+        );
+    }
+
+    #[test]
+    fn aligns_rows() {
+        // a 2045 pixel wide RGB image has rows of 6135 bytes
+        assert_eq!(unpack_alignment(2045 * 3), 1);
+        assert_eq!(unpack_alignment(2046 * 3), 2);
+        assert_eq!(unpack_alignment(2048 * 3), 8);
+        assert_eq!(unpack_alignment(1), 1);
     }
 }
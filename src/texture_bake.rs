@@ -0,0 +1,387 @@
+//! Baked texture format: images with their mip chains already made, and
+//! cubemaps already split into faces, so loading is just reading. Written by
+//! `texbake`, read by `texture::load` for `.tex` files.
+//!
+//! Layout, all numbers little-endian:
+//!
+//! ```text
+//! header   magic "SSTX", version u32, face count u8 (1, or 6 for a cubemap),
+//!          level count u8, channels u8, pixel type u8 (0 u8, 1 f32), sRGB u8
+//! images   per face in GL's order, per level from the full size one:
+//!          width u32, height u32, tightly packed pixels
+//! ```
+
+use crate::texture::{Image, Pixels, TextureImages};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub const MAGIC: [u8; 4] = *b"SSTX";
+pub const VERSION: u32 = 1;
+
+const PIXELS_U8: u8 = 0;
+const PIXELS_F32: u8 = 1;
+
+#[derive(Debug)]
+pub enum TextureBakeError {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u32),
+    Truncated,
+    Corrupt(&'static str),
+}
+
+impl fmt::Display for TextureBakeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureBakeError::Io(err) => write!(f, "{}", err),
+            TextureBakeError::BadMagic => write!(f, "not a baked texture file"),
+            TextureBakeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported version {} (expected {})", version, VERSION)
+            }
+            TextureBakeError::Truncated => write!(f, "file is truncated"),
+            TextureBakeError::Corrupt(what) => write!(f, "corrupt file: {}", what),
+        }
+    }
+}
+
+impl Error for TextureBakeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TextureBakeError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TextureBakeError {
+    fn from(err: io::Error) -> TextureBakeError {
+        TextureBakeError::Io(err)
+    }
+}
+
+/// Where the baked version of `source` lives: same path, `.tex` extension.
+pub fn cache_path(source: &str) -> String {
+    Path::new(source)
+        .with_extension("tex")
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// The full mip chain of `image`, down to 1x1, each level a box filtered
+/// half of the one before. With `srgb`, color channels are averaged as
+/// linear light and encoded again, so that mipmaps don't darken; alpha and
+/// float pixels are always linear. Compressed images are decompressed first.
+pub fn mip_chain(image: &Image, srgb: bool) -> Vec<Image> {
+    let mut levels = vec![image.decompressed()];
+
+    loop {
+        let last = &levels[levels.len() - 1];
+        if last.width == 1 && last.height == 1 {
+            return levels;
+        }
+
+        let next = downsample(last, srgb);
+        levels.push(next);
+    }
+}
+
+fn downsample(image: &Image, srgb: bool) -> Image {
+    let channels = image.channels as usize;
+    let (width, height) = (image.width as usize, image.height as usize);
+    let (half_width, half_height) = ((width / 2).max(1), (height / 2).max(1));
+    // alpha is the last channel of grey-alpha and RGBA images
+    let is_alpha = |channel: usize| channels % 2 == 0 && channel == channels - 1;
+
+    let value = |x: usize, y: usize, channel: usize| -> f32 {
+        let index = (y.min(height - 1) * width + x.min(width - 1)) * channels + channel;
+
+        match &image.pixels {
+            Pixels::U8(data) if srgb && !is_alpha(channel) => srgb_to_linear(data[index]),
+            Pixels::U8(data) => data[index] as f32 / 255.0,
+            Pixels::F32(data) => data[index],
+            Pixels::Compressed(..) => unreachable!("decompressed by mip_chain"),
+        }
+    };
+
+    let mut values = Vec::with_capacity(half_width * half_height * channels);
+    for y in 0..half_height {
+        for x in 0..half_width {
+            for channel in 0..channels {
+                let sum = value(2 * x, 2 * y, channel)
+                    + value(2 * x + 1, 2 * y, channel)
+                    + value(2 * x, 2 * y + 1, channel)
+                    + value(2 * x + 1, 2 * y + 1, channel);
+                values.push((channel, sum / 4.0));
+            }
+        }
+    }
+
+    let pixels = if image.pixels.is_float() {
+        Pixels::F32(values.into_iter().map(|(_, value)| value).collect())
+    } else {
+        Pixels::U8(
+            values
+                .into_iter()
+                .map(|(channel, value)| {
+                    if srgb && !is_alpha(channel) {
+                        linear_to_srgb(value)
+                    } else {
+                        (value * 255.0).round().clamp(0.0, 255.0) as u8
+                    }
+                })
+                .collect(),
+        )
+    };
+
+    Image {
+        width: half_width as u32,
+        height: half_height as u32,
+        channels: image.channels,
+        pixels,
+    }
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let value = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+
+    (value * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+pub fn save(images: &TextureImages, path: &str) -> Result<(), TextureBakeError> {
+    let first = &images.faces[0][0];
+    let mut bytes = vec![];
+
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.push(images.faces.len() as u8);
+    bytes.push(images.faces[0].len() as u8);
+    bytes.push(first.channels as u8);
+    bytes.push(if first.pixels.is_float() {
+        PIXELS_F32
+    } else {
+        PIXELS_U8
+    });
+    bytes.push(images.srgb as u8);
+
+    for image in images.faces.iter().flatten() {
+        // the format has no compressed pixels, blocks are stored decoded
+        let image = image.decompressed();
+        bytes.extend_from_slice(&image.width.to_le_bytes());
+        bytes.extend_from_slice(&image.height.to_le_bytes());
+
+        match &image.pixels {
+            Pixels::U8(data) => bytes.extend_from_slice(data),
+            Pixels::F32(data) => {
+                for value in data {
+                    bytes.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            Pixels::Compressed(..) => unreachable!(),
+        }
+    }
+
+    fs::write(path, bytes)?;
+
+    Ok(())
+}
+
+pub fn load(path: &str) -> Result<TextureImages, TextureBakeError> {
+    parse(&fs::read(path)?)
+}
+
+pub fn parse(bytes: &[u8]) -> Result<TextureImages, TextureBakeError> {
+    let mut reader = Reader { bytes, position: 0 };
+
+    if reader.take(4)? != MAGIC {
+        return Err(TextureBakeError::BadMagic);
+    }
+
+    let version = reader.u32()?;
+    if version != VERSION {
+        return Err(TextureBakeError::UnsupportedVersion(version));
+    }
+
+    let face_count = reader.u8()? as usize;
+    let level_count = reader.u8()? as usize;
+    let channels = reader.u8()? as u32;
+    let pixel_type = reader.u8()?;
+    let srgb = reader.u8()? != 0;
+
+    if face_count != 1 && face_count != 6 {
+        return Err(TextureBakeError::Corrupt("face count must be 1 or 6"));
+    }
+    if level_count == 0 {
+        return Err(TextureBakeError::Corrupt("no mip levels"));
+    }
+    if !(1..=4).contains(&channels) {
+        return Err(TextureBakeError::Corrupt("channels must be 1 to 4"));
+    }
+
+    let mut faces = Vec::with_capacity(face_count);
+    for _ in 0..face_count {
+        let mut levels = Vec::with_capacity(level_count);
+
+        for _ in 0..level_count {
+            let width = reader.u32()?;
+            let height = reader.u32()?;
+            let values = (width as usize)
+                .checked_mul(height as usize)
+                .and_then(|texels| texels.checked_mul(channels as usize))
+                .ok_or(TextureBakeError::Corrupt("image too large"))?;
+
+            let pixels = match pixel_type {
+                PIXELS_U8 => Pixels::U8(reader.take(values)?.to_vec()),
+                PIXELS_F32 => Pixels::F32(
+                    reader
+                        .take(values.checked_mul(4).ok_or(TextureBakeError::Truncated)?)?
+                        .chunks_exact(4)
+                        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                        .collect(),
+                ),
+                _ => return Err(TextureBakeError::Corrupt("unknown pixel type")),
+            };
+
+            levels.push(Image {
+                width,
+                height,
+                channels,
+                pixels,
+            });
+        }
+
+        faces.push(levels);
+    }
+
+    Ok(TextureImages { faces, srgb })
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], TextureBakeError> {
+        let end = self
+            .position
+            .checked_add(count)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or(TextureBakeError::Truncated)?;
+
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, TextureBakeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, TextureBakeError> {
+        let bytes = self.take(4)?;
+
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkerboard(size: u32) -> Image {
+        let pixels = (0..size * size)
+            .flat_map(|i| {
+                let on = (i % size + i / size) % 2 == 0;
+                let value = if on { 255 } else { 0 };
+                [value, value, value, 255]
+            })
+            .collect();
+
+        Image {
+            width: size,
+            height: size,
+            channels: 4,
+            pixels: Pixels::U8(pixels),
+        }
+    }
+
+    #[test]
+    fn averages_mipmaps_in_linear_light() {
+        let levels = mip_chain(&checkerboard(4), true);
+
+        assert_eq!(
+            levels
+                .iter()
+                .map(|level| (level.width, level.height))
+                .collect::<Vec<_>>(),
+            [(4, 4), (2, 2), (1, 1)]
+        );
+        // half white is 188 in sRGB, not 128, and alpha is left alone
+        assert_eq!(levels[2].pixels, Pixels::U8(vec![188, 188, 188, 255]));
+        assert_eq!(
+            mip_chain(&checkerboard(4), false)[2].pixels,
+            Pixels::U8(vec![128, 128, 128, 255])
+        );
+    }
+
+    #[test]
+    fn halves_odd_sizes_down_to_one_texel() {
+        let image = Image {
+            width: 5,
+            height: 2,
+            channels: 1,
+            pixels: Pixels::F32(vec![1.0; 10]),
+        };
+
+        let levels = mip_chain(&image, false);
+
+        assert_eq!(
+            levels
+                .iter()
+                .map(|level| (level.width, level.height))
+                .collect::<Vec<_>>(),
+            [(5, 2), (2, 1), (1, 1)]
+        );
+        assert_eq!(levels[2].pixels, Pixels::F32(vec![1.0]));
+    }
+
+    #[test]
+    fn round_trips_a_cubemap() {
+        let directory = std::env::temp_dir().join("solar-system-texture-bake-tests");
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("sky.tex");
+        let path = path.to_str().unwrap();
+        let images = TextureImages {
+            faces: (0..6).map(|_| mip_chain(&checkerboard(4), true)).collect(),
+            srgb: true,
+        };
+
+        save(&images, path).unwrap();
+
+        assert_eq!(load(path).unwrap(), images);
+        let bytes = fs::read(path).unwrap();
+        assert!(matches!(
+            parse(&bytes[..bytes.len() - 1]),
+            Err(TextureBakeError::Truncated)
+        ));
+        assert!(matches!(parse(b"SSMB"), Err(TextureBakeError::BadMagic)));
+    }
+}
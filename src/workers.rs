@@ -0,0 +1,157 @@
+//! A fixed pool of threads running jobs in the background, with the results
+//! collected on the thread that owns the pool.
+
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+type Job<R> = Box<dyn FnOnce() -> R + Send>;
+
+/// What a job returned, or the message it panicked with.
+pub type JobResult<R> = Result<R, String>;
+
+/// Runs jobs returning `R`, each tagged with an id given at submission so
+/// results, which arrive in completion order, can be matched up. Every job
+/// comes back, a panicking one as an error, so waiting never hangs.
+pub struct WorkerPool<R> {
+    jobs: Option<Sender<(usize, Job<R>)>>,
+    results: Receiver<(usize, JobResult<R>)>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<R: Send + 'static> WorkerPool<R> {
+    /// Starts `count` threads, at least one.
+    pub fn new(count: usize) -> WorkerPool<R> {
+        let (jobs, job_receiver) = mpsc::channel::<(usize, Job<R>)>();
+        let (result_sender, results) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let workers = (0..count.max(1))
+            .map(|i| {
+                let job_receiver = Arc::clone(&job_receiver);
+                let result_sender = result_sender.clone();
+
+                thread::Builder::new()
+                    .name(format!("worker {}", i))
+                    .spawn(move || loop {
+                        // the lock is only held while waiting, not while
+                        // running the job
+                        let job = job_receiver.lock().unwrap().recv();
+                        let (id, job) = match job {
+                            Ok(job) => job,
+                            Err(_) => break,
+                        };
+
+                        let result =
+                            panic::catch_unwind(AssertUnwindSafe(job)).map_err(panic_message);
+                        if result_sender.send((id, result)).is_err() {
+                            break;
+                        }
+                    })
+                    .expect("failed to start a worker thread")
+            })
+            .collect();
+
+        WorkerPool {
+            jobs: Some(jobs),
+            results,
+            workers,
+        }
+    }
+
+    /// One worker per core, leaving one for the render thread.
+    pub fn with_available_parallelism() -> WorkerPool<R> {
+        let cores = thread::available_parallelism().map_or(2, |cores| cores.get());
+        WorkerPool::new(cores - 1)
+    }
+
+    pub fn submit(&self, id: usize, job: impl FnOnce() -> R + Send + 'static) {
+        if let Some(jobs) = &self.jobs {
+            // only fails once every worker is gone, there is no one to report
+            // to then
+            let _ = jobs.send((id, Box::new(job)));
+        }
+    }
+
+    /// Results finished so far, without waiting.
+    pub fn finished(&self) -> Vec<(usize, JobResult<R>)> {
+        self.results.try_iter().collect()
+    }
+
+    /// Blocks until the next job finishes, so there has to be one still
+    /// queued or running.
+    pub fn wait(&self) -> Option<(usize, JobResult<R>)> {
+        self.results.recv().ok()
+    }
+}
+
+/// The message `panic!` was given, when it was a string.
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "job panicked".to_string(),
+        },
+    }
+}
+
+impl<R> Drop for WorkerPool<R> {
+    /// Lets queued jobs finish, then stops the threads.
+    fn drop(&mut self) {
+        self.jobs = None;
+
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_jobs_on_the_workers() {
+        let pool = WorkerPool::new(3);
+        for i in 0..20 {
+            pool.submit(i, move || {
+                let name = thread::current().name().unwrap().to_string();
+                (i * i, name)
+            });
+        }
+
+        let mut results: Vec<_> = (0..20).map(|_| pool.wait().unwrap()).collect();
+        results.sort_by_key(|(id, _)| *id);
+
+        for (i, (id, result)) in results.into_iter().enumerate() {
+            let (square, thread) = result.unwrap();
+            assert_eq!(id, i);
+            assert_eq!(square, i * i);
+            assert!(thread.starts_with("worker "));
+        }
+        assert!(pool.finished().is_empty());
+    }
+
+    #[test]
+    fn reports_panicking_jobs() {
+        let pool = WorkerPool::new(1);
+        pool.submit(0, || panic!("bad file {}", 7));
+        pool.submit(1, || -> usize { panic!("no format") });
+        pool.submit(2, || 2);
+
+        let mut results: Vec<_> = (0..3).map(|_| pool.wait().unwrap()).collect();
+        results.sort_by_key(|(id, _)| *id);
+
+        assert_eq!(
+            results,
+            [
+                (0, Err("bad file 7".to_string())),
+                (1, Err("no format".to_string())),
+                (2, Ok(2))
+            ]
+        );
+    }
+}